@@ -0,0 +1,105 @@
+# rustyterm shell integration for bash.
+#
+# rustyterm starts bash with --init-file pointing here, so this file loads
+# the usual startup files itself before adding its hooks.
+
+if [[ -n "${RUSTYTERM_BASH_LOGIN+x}" ]]; then
+    # A login shell ignores --init-file, so rustyterm starts a normal
+    # shell and the login files are read here
+    builtin unset RUSTYTERM_BASH_LOGIN
+    [[ -r /etc/profile ]] && builtin source /etc/profile
+    for __rustyterm_file in ~/.bash_profile ~/.bash_login ~/.profile; do
+        if [[ -r "$__rustyterm_file" ]]; then
+            builtin source "$__rustyterm_file"
+            break
+        fi
+    done
+    builtin unset __rustyterm_file
+else
+    # --init-file replaces both of the files bash reads by default
+    [[ -r /etc/bash.bashrc ]] && builtin source /etc/bash.bashrc
+    [[ -r ~/.bashrc ]] && builtin source ~/.bashrc
+fi
+
+if [[ -z "${__rustyterm_loaded-}" ]]; then
+    __rustyterm_loaded=1
+    __rustyterm_serial=0
+
+    # Percent-encode $1 into REPLY, byte by byte
+    __rustyterm_encode() {
+        local LC_ALL=C text=$1 safe
+        REPLY=
+        while [[ -n "$text" ]]; do
+            safe=${text%%[!a-zA-Z0-9._~-]*}
+            REPLY+=$safe
+            text=${text#"$safe"}
+            if [[ -n "$text" ]]; then
+                builtin printf -v safe '%%%02X' "'$text"
+                REPLY+=$safe
+                text=${text#?}
+            fi
+        done
+    }
+
+    # VTE does not report OSC 133 marks, so each one is repeated as an
+    # OSC 6 URI that rustyterm reads back
+    __rustyterm_mark() {
+        __rustyterm_serial=$((__rustyterm_serial + 1))
+        builtin printf '\e]6;file://rustyterm/%s/%s\a' "$__rustyterm_serial" "$1"
+    }
+
+    # Number of the newest history entry when the prompt was printed
+    __rustyterm_history=
+
+    # The newest history entry into REPLY, and its number into
+    # __rustyterm_number
+    __rustyterm_history_entry() {
+        REPLY=$(HISTTIMEFORMAT= builtin history 1)
+        REPLY=${REPLY#"${REPLY%%[! ]*}"}
+        __rustyterm_number=${REPLY%%[!0-9]*}
+        REPLY=${REPLY#"$__rustyterm_number"}
+        # Edited entries are marked with a star
+        REPLY=${REPLY#\*}
+        REPLY=${REPLY#"${REPLY%%[! ]*}"}
+    }
+
+    # The command line entered at the prompt, from the history. Empty when
+    # the history did not grow since then, as the command was not saved
+    # (HISTCONTROL=ignorespace, set +o history) and the newest entry is an
+    # older one.
+    __rustyterm_last_command() {
+        __rustyterm_history_entry
+        [[ "$__rustyterm_number" == "$__rustyterm_history" ]] && REPLY=
+    }
+
+    __rustyterm_precmd() {
+        local status=$?
+        # Set by PS0 when a command line was entered
+        if [[ -n "${__rustyterm_ran+x}" ]]; then
+            builtin unset __rustyterm_ran
+            __rustyterm_last_command
+            __rustyterm_encode "$REPLY"
+            builtin printf '\e]133;D;%s\a' "$status"
+            __rustyterm_mark "finish/$status/$REPLY"
+        else
+            __rustyterm_mark prompt
+        fi
+        __rustyterm_history_entry
+        __rustyterm_history=$__rustyterm_number
+        builtin printf '\e]133;A\a'
+        return $status
+    }
+
+    # Expanded by PS0 in a subshell, once the command line is read
+    __rustyterm_preexec() {
+        __rustyterm_last_command
+        __rustyterm_encode "$REPLY"
+        builtin printf '\e]133;C\a'
+        __rustyterm_mark "start/$REPLY"
+    }
+
+    PROMPT_COMMAND="__rustyterm_precmd${PROMPT_COMMAND:+; $PROMPT_COMMAND}"
+    # The assignment expands to nothing and happens in this shell, unlike
+    # the command substitution
+    PS0='${__rustyterm_ran:=}$(__rustyterm_preexec)'"${PS0-}"
+fi
@@ -0,0 +1,47 @@
+# rustyterm shell integration for fish.
+#
+# rustyterm adds a directory holding this file in fish/vendor_conf.d to
+# XDG_DATA_DIRS, and removes it again here.
+
+if set -q RUSTYTERM_XDG_DATA_DIRS
+    set -gx XDG_DATA_DIRS $RUSTYTERM_XDG_DATA_DIRS
+    set -e RUSTYTERM_XDG_DATA_DIRS
+else
+    set -e XDG_DATA_DIRS
+end
+
+status is-interactive; or exit
+
+set -g __rustyterm_serial 0
+
+# VTE does not report OSC 133 marks, so each one is repeated as an OSC 6
+# URI that rustyterm reads back
+function __rustyterm_mark
+    set -g __rustyterm_serial (math $__rustyterm_serial + 1)
+    printf '\e]6;file://rustyterm/%s/%s\a' $__rustyterm_serial $argv[1]
+end
+
+function __rustyterm_encode
+    string escape --style=url -- $argv[1] | string replace -a / %2F
+end
+
+function __rustyterm_preexec --on-event fish_preexec
+    set -g __rustyterm_command $argv[1]
+    printf '\e]133;C\a'
+    __rustyterm_mark start/(__rustyterm_encode $argv[1])
+end
+
+function __rustyterm_postexec --on-event fish_postexec
+    set -g __rustyterm_status $status
+end
+
+function __rustyterm_prompt --on-event fish_prompt
+    if set -q __rustyterm_command
+        printf '\e]133;D;%s\a' $__rustyterm_status
+        __rustyterm_mark finish/$__rustyterm_status/(__rustyterm_encode $__rustyterm_command)
+        set -e __rustyterm_command
+    else
+        __rustyterm_mark prompt
+    end
+    printf '\e]133;A\a'
+end
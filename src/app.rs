@@ -1,7 +1,18 @@
 use gtk4::prelude::*;
 use gtk4::{gio, glib, Application, CssProvider};
 use gtk4::gdk::Display;
+use gtk4::glib::translate::ToGlibPtr;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::rc::Rc;
 
+use crate::cli::{Action, Options};
+use crate::config::Config;
+use crate::keybindings::Keybindings;
+use crate::matchers;
+use crate::remote::RemoteControl;
+use crate::session::{Session, DEFAULT_SESSION};
+use crate::theme::{get_theme_by_name, load_themes};
 use crate::window::RustyTermWindow;
 
 const APP_ID: &str = "com.github.rustyterm";
@@ -10,15 +21,125 @@ pub struct RustyTermApp {
     app: Application,
 }
 
+/// State shared by the windows of the primary instance.
+#[derive(Clone)]
+struct Shared {
+    config: Rc<RefCell<Config>>,
+    windows: Rc<RefCell<Vec<Rc<RustyTermWindow>>>>,
+    startup_errors: Rc<RefCell<Vec<String>>>,
+    /// Startup errors about the config file, hidden once a reload succeeds
+    config_errors: Rc<RefCell<Vec<String>>>,
+    /// Session saved when the last window closes or the application quits
+    session: Rc<RefCell<String>>,
+}
+
 impl RustyTermApp {
-    pub fn new() -> Self {
+    /// Create the application for a process started with `options`.
+    /// Later launches hand their command line to the first instance,
+    /// except those with their own config file or window class.
+    pub fn new(options: &Options) -> Self {
+        let mut flags = gio::ApplicationFlags::HANDLES_COMMAND_LINE;
+        if options.config.is_some() || options.class.is_some() {
+            flags |= gio::ApplicationFlags::NON_UNIQUE;
+        }
+
         let app = Application::builder()
             .application_id(APP_ID)
-            .flags(gio::ApplicationFlags::empty())
+            .flags(flags)
             .build();
 
-        app.connect_startup(Self::on_startup);
-        app.connect_activate(Self::on_activate);
+        let shared = Shared {
+            // Configuration shared by every window, loaded at startup
+            config: Rc::new(RefCell::new(Config::default())),
+            // Open windows, most recent last
+            windows: Rc::new(RefCell::new(Vec::new())),
+            // Errors found before any window could show them
+            startup_errors: Rc::new(RefCell::new(Vec::new())),
+            config_errors: Rc::new(RefCell::new(Vec::new())),
+            session: Rc::new(RefCell::new(
+                options.session.clone().unwrap_or_else(|| DEFAULT_SESSION.to_string()),
+            )),
+        };
+
+        // Only the primary instance starts up, so only it loads the config,
+        // watches it and listens
+        let remote: Rc<RefCell<Option<RemoteControl>>> = Rc::new(RefCell::new(None));
+        let monitor: Rc<RefCell<Option<gio::FileMonitor>>> = Rc::new(RefCell::new(None));
+        let shared_clone = shared.clone();
+        let remote_clone = remote.clone();
+        let monitor_clone = monitor.clone();
+        app.connect_startup(move |app| {
+            let shared = &shared_clone;
+            Self::on_startup(app);
+            Self::setup_quit_action(app, shared);
+
+            match Config::load() {
+                Ok(loaded) => *shared.config.borrow_mut() = loaded,
+                Err(e) => shared.config_errors.borrow_mut().push(format!(
+                    "Failed to load configuration: {}\nUsing defaults. The file will not be overwritten until it is fixed.",
+                    e
+                )),
+            }
+            if let Some(message) = Self::apply_keybindings(app, &shared.config.borrow()) {
+                shared.config_errors.borrow_mut().push(message);
+            }
+            if let Some(message) = Self::check_matchers(&shared.config.borrow()) {
+                shared.config_errors.borrow_mut().push(message);
+            }
+            if let Some(message) = Self::check_themes(&shared.config.borrow()) {
+                shared.config_errors.borrow_mut().push(message);
+            }
+            match Self::setup_config_monitor(app, &shared.config, &shared.windows) {
+                Ok(file_monitor) => *monitor_clone.borrow_mut() = Some(file_monitor),
+                Err(e) => shared.startup_errors
+                    .borrow_mut()
+                    .push(format!("Cannot watch config file for changes: {}", e)),
+            }
+
+            if shared.config.borrow().remote_control {
+                match RemoteControl::listen(shared.windows.clone()) {
+                    Ok(control) => *remote_clone.borrow_mut() = Some(control),
+                    Err(e) => eprintln!("Cannot start remote control: {}", e),
+                }
+            }
+        });
+        app.connect_shutdown(move |_| {
+            if let Some(control) = remote.borrow_mut().take() {
+                control.stop();
+            }
+            if let Some(file_monitor) = monitor.borrow_mut().take() {
+                file_monitor.cancel();
+            }
+        });
+
+        // Save the session when the last window is closed. The handler runs
+        // after those of the window, so not when its close is cancelled.
+        let shared_clone = shared.clone();
+        app.connect_window_added(move |_, window| {
+            let shared = shared_clone.clone();
+            window.connect_closure(
+                "close-request",
+                true,
+                glib::closure_local!(move |window: &gtk4::Window| -> bool {
+                    let windows = shared.windows.borrow();
+                    if let [last] = windows.as_slice()
+                        && last.window.upcast_ref::<gtk4::Window>() == window
+                    {
+                        Self::save_session(&shared.session.borrow(), &windows);
+                    }
+                    false
+                }),
+            );
+        });
+
+        let shared_clone = shared.clone();
+        app.connect_activate(move |app| {
+            Self::open_window(app, &shared_clone, &Options::default());
+        });
+
+        app.connect_command_line(move |app, command_line| {
+            Self::on_command_line(app, command_line, &shared).into()
+        });
 
         Self { app }
     }
@@ -55,6 +176,32 @@ impl RustyTermApp {
             .add-tab-button:active {
                 background-color: rgba(255, 255, 255, 0.2);
             }
+
+            /* In-window notification banner */
+            .banner {
+                padding: 6px 12px;
+                color: #ffffff;
+            }
+            .banner.error {
+                background-color: #e01b24;
+            }
+            .banner.info {
+                background-color: #1c71d8;
+            }
+
+            /* Scrollback search bar floating over the terminal */
+            .search-bar {
+                margin: 6px 12px;
+                padding: 4px;
+                border-radius: 6px;
+            }
+
+            /* Target of the hyperlink under the pointer */
+            .hyperlink-preview {
+                margin: 4px;
+                padding: 2px 8px;
+                border-radius: 4px;
+            }
             "#,
         );
 
@@ -67,12 +214,338 @@ impl RustyTermApp {
         }
     }
 
-    fn on_activate(app: &Application) {
-        let window = RustyTermWindow::new(app);
+    /// Quit after asking once about the commands running in any window.
+    /// The session is saved with every window still open.
+    fn setup_quit_action(app: &Application, shared: &Shared) {
+        let quit_action = gio::SimpleAction::new("quit", None);
+        let app_clone = app.clone();
+        let shared = shared.clone();
+        quit_action.connect_activate(move |_, _| {
+            let processes: Vec<String> = shared.windows.borrow().iter()
+                .flat_map(|window| window.running_processes())
+                .collect();
+            let app = app_clone.clone();
+            let shared_clone = shared.clone();
+            let quit = move || {
+                Self::save_session(&shared_clone.session.borrow(), &shared_clone.windows.borrow());
+                // Quitting skips close-request, where windows remember their
+                // size, so close them in order and remember the last one
+                let windows = shared_clone.windows.borrow().clone();
+                if let Some(last) = windows.last() {
+                    last.remember_size();
+                }
+                for window in windows {
+                    window.window.destroy();
+                }
+                app.quit();
+            };
+            match Self::active_window(&app_clone, &shared.windows) {
+                Some(window) => RustyTermWindow::confirm_close(
+                    &window.window,
+                    "Quit RustyTerm?",
+                    &processes,
+                    quit,
+                ),
+                None => quit(),
+            }
+        });
+        app.add_action(&quit_action);
+    }
+
+    /// Watch config.toml and apply changes to every window.
+    fn setup_config_monitor(
+        app: &Application,
+        config: &Rc<RefCell<Config>>,
+        windows: &Rc<RefCell<Vec<Rc<RustyTermWindow>>>>,
+    ) -> Result<gio::FileMonitor, glib::Error> {
+        // The monitor needs the directory to exist to notice the file being created
+        if let Some(dir) = Config::config_file().parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+
+        let file = gio::File::for_path(Config::config_file());
+        let monitor = file.monitor_file(gio::FileMonitorFlags::NONE, gio::Cancellable::NONE)?;
+
+        let app = app.clone();
+        let config = config.clone();
+        let windows = windows.clone();
+        monitor.connect_changed(move |_, _, _, event| {
+            if matches!(event, gio::FileMonitorEvent::ChangesDoneHint | gio::FileMonitorEvent::Created) {
+                Self::reload_config(&app, &config, &windows.borrow());
+            }
+        });
+        Ok(monitor)
+    }
+
+    fn reload_config(app: &Application, config: &Rc<RefCell<Config>>, windows: &[Rc<RustyTermWindow>]) {
+        let new_config = match Config::load() {
+            Ok(new_config) => new_config,
+            Err(e) => {
+                for window in windows {
+                    window.banner.show_config_error(&format!(
+                        "Failed to reload configuration: {}\nKeeping the previous configuration.",
+                        e
+                    ));
+                }
+                return;
+            }
+        };
+
+        for window in windows {
+            window.banner.hide_config_error();
+        }
+        if *config.borrow() == new_config {
+            return;
+        }
+
+        // The banner holds a single message, so everything to report is
+        // collected and shown once
+        let mut errors = Vec::new();
+        // Theme files may have changed along with the config
+        let (_, theme_errors) = load_themes();
+        if !theme_errors.is_empty() {
+            let messages: Vec<String> = theme_errors.iter().map(|e| e.to_string()).collect();
+            errors.push(format!("Failed to load themes:\n{}", messages.join("\n")));
+        }
+        errors.extend(Self::check_themes(&new_config));
+
+        for window in windows {
+            window.apply_config(&new_config);
+        }
+        if new_config.keybindings != config.borrow().keybindings {
+            errors.extend(Self::apply_keybindings(app, &new_config));
+        }
+        if new_config.matchers != config.borrow().matchers {
+            errors.extend(Self::check_matchers(&new_config));
+        }
+
+        let mut messages = errors.clone();
+        // The remote control socket is only opened at startup
+        let (restart_fields, new_tab_fields): (Vec<&str>, Vec<&str>) = new_config
+            .restart_required_changes(&config.borrow())
+            .into_iter()
+            .partition(|field| *field == "remote_control");
+        if !new_tab_fields.is_empty() {
+            messages.push(format!(
+                "Configuration reloaded. {} will apply to new windows and tabs.",
+                new_tab_fields.join(", ")
+            ));
+        }
+        if !restart_fields.is_empty() {
+            messages.push(format!(
+                "Configuration reloaded. {} will apply once rustyterm restarts.",
+                restart_fields.join(", ")
+            ));
+        }
+        if !messages.is_empty() {
+            let message = messages.join("\n");
+            for window in windows {
+                if errors.is_empty() {
+                    window.banner.show_info(&message);
+                } else {
+                    window.banner.show_config_error(&message);
+                }
+            }
+        }
+
+        *config.borrow_mut() = new_config;
+    }
+
+    /// Set the shortcuts of every action, returning a message describing
+    /// the `[keybindings]` entries that were ignored or that leave an
+    /// action without a shortcut.
+    fn apply_keybindings(app: &Application, config: &Config) -> Option<String> {
+        let (keybindings, errors) = Keybindings::new(&config.keybindings);
+        keybindings.apply(app);
+        let mut sections = Vec::new();
+        if !errors.is_empty() {
+            let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            sections.push(format!("Ignored invalid keybindings:\n{}", messages.join("\n")));
+        }
+        if !keybindings.shadowed.is_empty() {
+            sections.push(format!(
+                "Keybindings replacing the only shortcut of an action:\n{}",
+                keybindings.shadowed.join("\n")
+            ));
+        }
+        if sections.is_empty() {
+            return None;
+        }
+        Some(sections.join("\n"))
+    }
+
+    /// Describe the `[[matchers]]` entries that terminals skip.
+    fn check_matchers(config: &Config) -> Option<String> {
+        let errors = matchers::check(&config.matchers);
+        if errors.is_empty() {
+            return None;
+        }
+        Some(format!("Ignored invalid matchers:\n{}", errors.join("\n")))
+    }
+
+    /// Describe the themes named by the config that do not exist.
+    fn check_themes(config: &Config) -> Option<String> {
+        let names = std::iter::once(&config.theme)
+            .chain(config.profiles.iter().filter_map(|profile| profile.theme.as_ref()));
+        let unknown: Vec<String> = names
+            .filter(|name| get_theme_by_name(name).is_none())
+            .map(|name| format!("Unknown theme \"{}\"", name))
+            .collect();
+        if unknown.is_empty() {
+            return None;
+        }
+        Some(format!("Ignored invalid themes:\n{}", unknown.join("\n")))
+    }
+
+    fn save_session(name: &str, windows: &[Rc<RustyTermWindow>]) {
+        let session = Session {
+            windows: windows.iter().map(|window| window.state()).collect(),
+        };
+        if let Err(e) = session.save(name) {
+            eprintln!("Failed to save session: {}", e);
+        }
+    }
+
+    /// Open a window for `options`. The first window restores the session
+    /// instead when the config asks for it, and `--session` always does.
+    fn open_window(app: &Application, shared: &Shared, options: &Options) {
+        let session = match &options.session {
+            Some(name) => Some(name.clone()),
+            None if shared.windows.borrow().is_empty()
+                && shared.config.borrow().restore_session
+                && options.tab.command.is_none() =>
+            {
+                Some(shared.session.borrow().clone())
+            }
+            None => None,
+        };
+        if let Some(name) = session
+            && Self::restore_session(app, shared, &name)
+        {
+            return;
+        }
+
+        let window = RustyTermWindow::new(app, &shared.config, &shared.windows, options);
+        Self::show_startup_errors(shared, &window);
         window.present();
     }
 
+    /// Reopen the windows of a saved session, returning false if it has none.
+    fn restore_session(app: &Application, shared: &Shared, name: &str) -> bool {
+        let session = match Session::load(name) {
+            Ok(Some(session)) => session,
+            Ok(None) => return false,
+            Err(e) => {
+                shared.startup_errors.borrow_mut().push(format!("Failed to restore session: {}", e));
+                return false;
+            }
+        };
+
+        for state in &session.windows {
+            let window = RustyTermWindow::restore(app, &shared.config, &shared.windows, state);
+            Self::show_startup_errors(shared, &window);
+            window.present();
+        }
+        !session.windows.is_empty()
+    }
+
+    fn show_startup_errors(shared: &Shared, window: &RustyTermWindow) {
+        let config_errors = std::mem::take(&mut *shared.config_errors.borrow_mut());
+        let errors = std::mem::take(&mut *shared.startup_errors.borrow_mut());
+        if errors.is_empty() {
+            if !config_errors.is_empty() {
+                window.banner.show_config_error(&config_errors.join("\n"));
+            }
+        } else {
+            window.banner.show_error(&[config_errors, errors].concat().join("\n"));
+        }
+    }
+
+    /// The window that last had focus.
+    fn active_window(
+        app: &Application,
+        windows: &Rc<RefCell<Vec<Rc<RustyTermWindow>>>>,
+    ) -> Option<Rc<RustyTermWindow>> {
+        let active = app.active_window()?;
+        windows.borrow().iter()
+            .find(|w| w.window.upcast_ref::<gtk4::Window>() == &active)
+            .cloned()
+    }
+
+    /// Carry out a command line, which may come from another process when
+    /// rustyterm is already running. Its exit code is returned to that process.
+    fn on_command_line(
+        app: &Application,
+        command_line: &gio::ApplicationCommandLine,
+        shared: &Shared,
+    ) -> glib::ExitCode {
+        let args: Vec<String> = command_line
+            .arguments()
+            .iter()
+            .skip(1)
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        let mut options = match Options::parse(&args) {
+            Ok(options) => options,
+            Err(e) => {
+                // main() already rejects invalid arguments before forwarding them
+                printerr(command_line, &format!("{}\n", e));
+                return glib::ExitCode::FAILURE;
+            }
+        };
+
+        if let Some(profile) = &options.tab.profile
+            && !shared.config.borrow().has_profile(profile)
+        {
+            printerr(command_line, &format!("Unknown profile \"{}\"\n", profile));
+            return glib::ExitCode::FAILURE;
+        }
+
+        // Paths are relative to the directory of the launching process
+        if let Some(cwd) = command_line.cwd() {
+            if let Some(dir) = &options.tab.working_directory {
+                options.tab.working_directory = Some(cwd.join(dir));
+            }
+            options.tab.parent_directory = Some(cwd);
+        }
+
+        match (options.action, Self::active_window(app, &shared.windows)) {
+            (Action::NewTab, Some(window)) => {
+                window.add_tab(&options.tab);
+                window.present();
+            }
+            (Action::FocusTab(index), Some(window)) => {
+                if !window.focus_tab(index) {
+                    printerr(command_line, &format!("There is no tab {}\n", index + 1));
+                    return glib::ExitCode::FAILURE;
+                }
+                window.present();
+            }
+            (Action::FocusTab(_), None) => {
+                printerr(command_line, "There is no window to switch tabs in\n");
+                return glib::ExitCode::FAILURE;
+            }
+            // A new tab without a window gets a new window
+            (Action::NewWindow | Action::NewTab, _) => Self::open_window(app, shared, &options),
+        }
+        glib::ExitCode::SUCCESS
+    }
+
     pub fn run(&self) -> glib::ExitCode {
         self.app.run()
     }
 }
+
+/// Print `message` on the stderr of the process that sent `command_line`.
+/// `printerr_literal` needs GLib 2.80, so this passes the message to the
+/// printf-like `printerr` through a "%s" format instead.
+fn printerr(command_line: &gio::ApplicationCommandLine, message: &str) {
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    unsafe {
+        gio::ffi::g_application_command_line_printerr(
+            command_line.to_glib_none().0,
+            c"%s".as_ptr(),
+            message.as_ptr(),
+        );
+    }
+}
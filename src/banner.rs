@@ -0,0 +1,92 @@
+//! In-window notification banner.
+//!
+//! This module provides a dismissable bar shown above the tabs, used to
+//! report problems such as broken configuration or theme files. Panes use
+//! the same bar on top of their terminal to report how its process ended.
+
+use gtk4::prelude::*;
+use gtk4::{Box, Button, Label, Orientation, Revealer, RevealerTransitionType};
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// A banner that slides in at the top of the window.
+#[derive(Clone)]
+pub struct Banner {
+    pub revealer: Revealer,
+    content: Box,
+    label: Label,
+    /// Whether the message shown reports a problem in the config file
+    config_error: Rc<Cell<bool>>,
+}
+
+impl Banner {
+    pub fn new() -> Self {
+        let label = Label::new(None);
+        label.set_wrap(true);
+        label.set_xalign(0.0);
+        label.set_selectable(true);
+        label.set_hexpand(true);
+
+        let close_button = Button::from_icon_name("window-close-symbolic");
+        close_button.set_has_frame(false);
+        close_button.set_valign(gtk4::Align::Start);
+
+        let content = Box::new(Orientation::Horizontal, 8);
+        content.add_css_class("banner");
+        content.append(&label);
+        content.append(&close_button);
+
+        let revealer = Revealer::new();
+        revealer.set_transition_type(RevealerTransitionType::SlideDown);
+        revealer.set_child(Some(&content));
+
+        let revealer_clone = revealer.clone();
+        close_button.connect_clicked(move |_| {
+            revealer_clone.set_reveal_child(false);
+        });
+
+        Self { revealer, content, label, config_error: Rc::new(Cell::new(false)) }
+    }
+
+    /// Show an error message, replacing any message currently displayed.
+    pub fn show_error(&self, message: &str) {
+        self.show(message, "error");
+    }
+
+    /// Show an error about the config file, which `hide_config_error`
+    /// removes once the file is fixed.
+    pub fn show_config_error(&self, message: &str) {
+        self.show(message, "error");
+        self.config_error.set(true);
+    }
+
+    /// Show an informational message, replacing any message currently displayed.
+    pub fn show_info(&self, message: &str) {
+        self.show(message, "info");
+    }
+
+    /// Add a button between the message and the close button.
+    pub fn append_button(&self, button: &Button) {
+        self.content.insert_child_after(button, Some(&self.label));
+    }
+
+    pub fn hide(&self) {
+        self.revealer.set_reveal_child(false);
+    }
+
+    /// Hide the banner if it shows an error about the config file.
+    pub fn hide_config_error(&self) {
+        if self.config_error.get() {
+            self.hide();
+        }
+    }
+
+    fn show(&self, message: &str, css_class: &str) {
+        self.content.remove_css_class("error");
+        self.content.remove_css_class("info");
+        self.content.add_css_class(css_class);
+        self.label.set_text(message);
+        self.config_error.set(false);
+        self.revealer.set_reveal_child(true);
+    }
+}
@@ -0,0 +1,275 @@
+//! Command line options.
+//!
+//! Options are parsed by hand rather than with GOption because `-e` takes
+//! every remaining argument as the command to run, as in xterm.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::session;
+use crate::tab::TabOptions;
+
+pub const USAGE: &str = "\
+Usage: rustyterm [OPTION…] [-e COMMAND [ARG…]]
+
+Options:
+  --new-window                  Open a new window (the default)
+  --new-tab                     Open a tab in the last focused window
+  --focus-tab N                 Switch the last focused window to tab N
+  -e, --command COMMAND [ARG…]  Run COMMAND instead of the shell; every
+                                following argument is passed to it
+  --working-directory DIR       Start in DIR
+  --title TITLE                 Set the title of the tab and window
+  --profile NAME                Use the named profile
+  --hold                        Keep the terminal open after the command exits
+  --geometry COLSxROWS          Size the window to fit COLS columns and ROWS rows
+  --session NAME                Reopen the windows saved in session NAME and
+                                save them there when quitting
+  --config FILE                 Read settings from FILE instead of config.toml
+  --class CLASS                 Set the window class used by window managers
+  -h, --help                    Show this help
+
+Theme tools:
+  --import-theme FILE           Convert a color scheme and save it as a theme
+  --export-theme FORMAT [THEME] Print a theme in another terminal's format
+  --print-default-config        Print a commented default config.toml
+";
+
+/// What a launch asks the running instance to do.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Action {
+    #[default]
+    NewWindow,
+    NewTab,
+    /// Switch to the tab at this 0-based index
+    FocusTab(usize),
+}
+
+/// A theme tool to run instead of opening a terminal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tool {
+    ImportTheme(PathBuf),
+    /// Print `theme`, or the configured one, in the format named `format`
+    ExportTheme { format: String, theme: Option<String> },
+    PrintDefaultConfig,
+}
+
+/// Options given on the command line.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub action: Action,
+    /// The new tab, or the first tab of the new window
+    pub tab: TabOptions,
+    /// Columns and rows of the first terminal
+    pub geometry: Option<(i64, i64)>,
+    /// Saved session to reopen
+    pub session: Option<String>,
+    pub config: Option<PathBuf>,
+    pub class: Option<String>,
+    pub tool: Option<Tool>,
+    pub help: bool,
+}
+
+/// Invalid command line.
+#[derive(Debug)]
+pub struct CliError(String);
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl Options {
+    /// Parse the arguments that follow the program name.
+    pub fn parse(args: &[String]) -> Result<Self, CliError> {
+        let mut options = Self::default();
+        let mut action = None;
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            // Accept both "--option value" and "--option=value"
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next().cloned())
+                    .ok_or_else(|| CliError(format!("{} requires a value", name)))
+            };
+
+            match name {
+                "-e" | "--command" => {
+                    let mut command: Vec<String> = inline_value.into_iter().collect();
+                    command.extend(args.by_ref().cloned());
+                    options.tab.command = Some(parse_command(command)?);
+                }
+                "--new-window" => set_action(&mut action, Action::NewWindow)?,
+                "--new-tab" => set_action(&mut action, Action::NewTab)?,
+                "--focus-tab" => {
+                    let tab = value()?;
+                    let index = tab.parse::<usize>().ok()
+                        .and_then(|n| n.checked_sub(1))
+                        .ok_or_else(|| CliError(format!("Invalid tab number \"{}\"", tab)))?;
+                    set_action(&mut action, Action::FocusTab(index))?;
+                }
+                "--working-directory" => options.tab.working_directory = Some(value()?.into()),
+                "--title" => options.tab.title = Some(value()?),
+                "--profile" => options.tab.profile = Some(value()?),
+                "--hold" => options.tab.hold = true,
+                "--geometry" => options.geometry = Some(parse_geometry(&value()?)?),
+                "--session" => {
+                    let name = value()?;
+                    if !session::is_valid_name(&name) {
+                        return Err(CliError(format!("Invalid session name \"{}\"", name)));
+                    }
+                    options.session = Some(name);
+                }
+                "--config" => options.config = Some(value()?.into()),
+                "--class" => options.class = Some(value()?),
+                "--import-theme" => set_tool(&mut options.tool, Tool::ImportTheme(value()?.into()))?,
+                "--export-theme" => {
+                    let format = value()?;
+                    // The theme is optional, so only a following non-option is taken
+                    let theme = match args.as_slice().first() {
+                        Some(theme) if inline_value.is_none() && !theme.starts_with('-') => args.next().cloned(),
+                        _ => None,
+                    };
+                    set_tool(&mut options.tool, Tool::ExportTheme { format, theme })?;
+                }
+                "--print-default-config" => set_tool(&mut options.tool, Tool::PrintDefaultConfig)?,
+                "-h" | "--help" => options.help = true,
+                _ => return Err(CliError(format!("Unknown option \"{}\"", arg))),
+            }
+        }
+
+        options.action = action.unwrap_or_default();
+        Ok(options)
+    }
+}
+
+fn set_action(action: &mut Option<Action>, new: Action) -> Result<(), CliError> {
+    match action {
+        Some(current) if *current != new => Err(CliError(
+            "Only one of --new-window, --new-tab and --focus-tab may be given".to_string(),
+        )),
+        _ => {
+            *action = Some(new);
+            Ok(())
+        }
+    }
+}
+
+fn set_tool(tool: &mut Option<Tool>, new: Tool) -> Result<(), CliError> {
+    if tool.is_some() {
+        return Err(CliError(
+            "Only one of --import-theme, --export-theme and --print-default-config may be given".to_string(),
+        ));
+    }
+    *tool = Some(new);
+    Ok(())
+}
+
+/// A single argument after `-e` is split like a shell would, so that
+/// `-e "vim file"` from launchers expecting xterm works too.
+fn parse_command(command: Vec<String>) -> Result<Vec<String>, CliError> {
+    match command.as_slice() {
+        [] => Err(CliError("-e requires a command".to_string())),
+        [line] => glib::shell_parse_argv(line.as_str())
+            .map(|argv| argv.iter().map(|arg| arg.to_string_lossy().into_owned()).collect())
+            .map_err(|e| CliError(format!("Invalid command \"{}\": {}", line, e))),
+        _ => Ok(command),
+    }
+}
+
+/// Parse `COLSxROWS`, such as `80x24`.
+fn parse_geometry(geometry: &str) -> Result<(i64, i64), CliError> {
+    let invalid = || CliError(format!("Invalid geometry \"{}\", expected COLSxROWS", geometry));
+    let (cols, rows) = geometry.split_once('x').ok_or_else(invalid)?;
+    let cols: i64 = cols.parse().map_err(|_| invalid())?;
+    let rows: i64 = rows.parse().map_err(|_| invalid())?;
+    if cols <= 0 || rows <= 0 {
+        return Err(invalid());
+    }
+    Ok((cols, rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, CliError> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn options_take_separate_or_inline_values() {
+        let options = parse(&["--title", "logs", "--working-directory=/tmp", "--hold"]).unwrap();
+        assert_eq!(options.tab.title.as_deref(), Some("logs"));
+        assert_eq!(options.tab.working_directory, Some(PathBuf::from("/tmp")));
+        assert!(options.tab.hold);
+        assert_eq!(options.action, Action::NewWindow);
+        assert!(parse(&["--title"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+        assert!(parse(&["--session", "../work"]).is_err());
+    }
+
+    #[test]
+    fn command_takes_every_remaining_argument() {
+        let options = parse(&["--new-tab", "-e", "ssh", "host", "--title", "x"]).unwrap();
+        assert_eq!(options.action, Action::NewTab);
+        assert_eq!(options.tab.command, Some(vec!["ssh".into(), "host".into(), "--title".into(), "x".into()]));
+        assert_eq!(options.tab.title, None);
+        assert!(parse(&["-e"]).is_err());
+    }
+
+    #[test]
+    fn single_command_argument_is_split_like_a_shell() {
+        assert_eq!(parse_command(vec!["vim 'my file.txt'".into()]).unwrap(), ["vim", "my file.txt"]);
+        assert_eq!(parse_command(vec!["vim".into(), "my file.txt".into()]).unwrap(), ["vim", "my file.txt"]);
+        assert!(parse_command(vec!["vim 'unterminated".into()]).is_err());
+        assert!(parse_command(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn tab_numbers_are_one_based_and_actions_exclusive() {
+        assert_eq!(parse(&["--focus-tab", "2"]).unwrap().action, Action::FocusTab(1));
+        assert!(parse(&["--focus-tab", "0"]).is_err());
+        assert!(parse(&["--focus-tab", "two"]).is_err());
+        assert!(parse(&["--new-tab", "--new-window"]).is_err());
+        assert_eq!(parse(&["--new-tab", "--new-tab"]).unwrap().action, Action::NewTab);
+    }
+
+    #[test]
+    fn geometry_is_columns_by_rows() {
+        assert_eq!(parse_geometry("80x24").unwrap(), (80, 24));
+        assert!(parse_geometry("80").is_err());
+        assert!(parse_geometry("0x24").is_err());
+        assert!(parse_geometry("80x-1").is_err());
+        assert!(parse_geometry("wide x tall").is_err());
+        assert_eq!(parse(&["--geometry=100x30"]).unwrap().geometry, Some((100, 30)));
+    }
+
+    #[test]
+    fn theme_tools_combine_with_other_options() {
+        let options = parse(&["--config", "x.toml", "--export-theme", "kitty"]).unwrap();
+        assert_eq!(options.config, Some(PathBuf::from("x.toml")));
+        assert_eq!(options.tool, Some(Tool::ExportTheme { format: "kitty".into(), theme: None }));
+        let options = parse(&["--export-theme", "kitty", "dracula", "--config", "x.toml"]).unwrap();
+        assert_eq!(options.tool, Some(Tool::ExportTheme { format: "kitty".into(), theme: Some("dracula".into()) }));
+        assert_eq!(options.config, Some(PathBuf::from("x.toml")));
+        assert_eq!(
+            parse(&["--export-theme=kitty", "--class", "x"]).unwrap().tool,
+            Some(Tool::ExportTheme { format: "kitty".into(), theme: None })
+        );
+        assert_eq!(parse(&["--import-theme", "a.conf"]).unwrap().tool, Some(Tool::ImportTheme("a.conf".into())));
+        assert_eq!(parse(&["--print-default-config"]).unwrap().tool, Some(Tool::PrintDefaultConfig));
+        assert!(parse(&["--import-theme"]).is_err());
+        assert!(parse(&["--print-default-config", "--import-theme", "a.conf"]).is_err());
+    }
+}
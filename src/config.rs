@@ -1,30 +1,227 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Config file chosen with `--config`, replacing the default location.
+static CONFIG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Current config schema version, bumped whenever a migration is added.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Migrations between schema versions: entry `n` upgrades a version `n`
+/// table to version `n + 1`.
+const MIGRATIONS: [fn(&mut toml::Table); CONFIG_VERSION as usize] = [migrate_v0_to_v1];
+
+/// Files written before the `version` key existed. Every field already
+/// matches version 1, so only the key itself is added.
+fn migrate_v0_to_v1(_table: &mut toml::Table) {}
+
+/// Settings loaded from config.toml. Missing keys take their default value,
+/// so a file may set only the fields it cares about.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
+    pub version: u32,
     pub theme: String,
     pub font_family: String,
     pub font_size: u32,
     pub scrollback_lines: i64,
     pub window_width: i32,
     pub window_height: i32,
+    pub window_maximized: bool,
+    pub window_fullscreen: bool,
+    /// Size new windows to fit this grid instead of the size in pixels
+    pub window_columns: Option<i64>,
+    pub window_rows: Option<i64>,
+    /// Open new windows with the size and state of the last one closed
+    pub remember_window_size: bool,
+    /// Open new tabs and splits in the focused terminal's directory
+    pub inherit_working_directory: bool,
+    /// Load the scripts that mark prompts and commands into bash, zsh and fish
+    pub shell_integration: bool,
+    /// Foreground processes that may be closed without confirmation
+    pub confirm_close_ignore: Vec<String>,
+    /// Accept commands on the socket named by `RUSTYTERM_LISTEN_ON`
+    pub remote_control: bool,
+    /// Reopen the windows of the last session at startup
+    pub restore_session: bool,
+    /// Profile used by the new tab button and shortcut
+    pub default_profile: String,
+    pub profiles: Vec<Profile>,
+    /// Accelerators mapped to action names, on top of the default shortcuts
+    pub keybindings: BTreeMap<String, String>,
+    /// Clickable patterns, checked before the built-in ones
+    pub matchers: Vec<Matcher>,
+}
+
+/// A named set of shell and appearance settings that a tab is opened with.
+/// Appearance fields left unset fall back to the global settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    /// Program to run instead of `$SHELL`
+    pub command: Option<String>,
+    pub args: Vec<String>,
+    pub working_directory: Option<String>,
+    /// Variables set in addition to the inherited environment
+    pub env: BTreeMap<String, String>,
+    /// Inherited variables removed from the environment
+    pub unset_env: Vec<String>,
+    /// Run the command as a login shell
+    pub login_shell: bool,
+    pub on_exit: ExitAction,
+    pub theme: Option<String>,
+    pub font_family: Option<String>,
+    pub font_size: Option<u32>,
+    pub scrollback_lines: Option<i64>,
+}
+
+/// What happens to a pane when its process exits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExitAction {
+    /// Close the pane, or the tab if it is the last pane
+    #[default]
+    Close,
+    /// Keep the pane open and show the exit status
+    Hold,
+    /// Keep the pane open with a button to start the command again
+    Restart,
+}
+
+/// Text in terminal output that is underlined on hover and runs `action`
+/// on Ctrl+click.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Matcher {
+    /// PCRE2 regular expression
+    pub regex: String,
+    pub action: MatchAction,
+    /// Program and arguments run by the `command` action, where `{}` stands
+    /// for the matched text
+    pub command: Option<String>,
+}
+
+/// What Ctrl+clicking a match does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchAction {
+    /// Open with the default application for the URL or file
+    #[default]
+    Open,
+    /// Open a `path:line:col` location in `$EDITOR`
+    Edit,
+    /// Copy the text to the clipboard
+    Copy,
+    /// Run the matcher's `command`
+    Command,
+}
+
+impl Profile {
+    /// Whether settings that only apply when a terminal is spawned differ.
+    fn spawn_settings_differ(&self, other: &Profile) -> bool {
+        self.command != other.command
+            || self.args != other.args
+            || self.working_directory != other.working_directory
+            || self.env != other.env
+            || self.unset_env != other.unset_env
+            || self.login_shell != other.login_shell
+            || self.on_exit != other.on_exit
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             theme: "default".to_string(),
             font_family: "Monospace".to_string(),
             font_size: 12,
             scrollback_lines: 10000,
             window_width: 800,
             window_height: 600,
+            window_maximized: false,
+            window_fullscreen: false,
+            window_columns: None,
+            window_rows: None,
+            remember_window_size: false,
+            inherit_working_directory: true,
+            shell_integration: true,
+            confirm_close_ignore: ["bash", "zsh", "fish", "sh", "tmux", "screen"]
+                .map(String::from)
+                .to_vec(),
+            remote_control: true,
+            restore_session: false,
+            default_profile: "Default".to_string(),
+            profiles: Vec::new(),
+            keybindings: BTreeMap::new(),
+            matchers: Vec::new(),
+        }
+    }
+}
+
+/// Error raised while reading or writing the config file.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        /// 1-based position of the error, when toml reports one
+        location: Option<(usize, usize)>,
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ConfigError::Parse { path, location: Some((line, column)), message } => {
+                write!(f, "{}:{}:{}: {}", path.display(), line, column, message)
+            }
+            ConfigError::Parse { path, location: None, message } => {
+                write!(f, "{}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ConfigError {
+    fn io(path: &Path, source: io::Error) -> Self {
+        ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    fn parse(path: &Path, content: &str, error: toml::de::Error) -> Self {
+        let location = error.span().map(|span| line_column(content, span.start));
+        ConfigError::Parse {
+            path: path.to_path_buf(),
+            location,
+            message: error.message().trim().to_string(),
         }
     }
 }
 
+/// Convert a byte offset into a 1-based line and column.
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    (line, column)
+}
+
 impl Config {
     pub fn config_dir() -> PathBuf {
         dirs::config_dir()
@@ -33,26 +230,480 @@ impl Config {
     }
 
     pub fn config_file() -> PathBuf {
-        Self::config_dir().join("config.toml")
+        CONFIG_FILE
+            .get()
+            .cloned()
+            .unwrap_or_else(|| Self::config_dir().join("config.toml"))
+    }
+
+    /// Read and save settings from `path` instead of the default config
+    /// file. Only the first call has an effect.
+    pub fn set_config_file(path: PathBuf) {
+        let _ = CONFIG_FILE.set(path);
+    }
+
+    /// Copy of the previous config file, written before every save.
+    pub fn backup_file() -> PathBuf {
+        Self::sibling_file("bak")
+    }
+
+    /// `config.toml.<extension>` next to the config file.
+    fn sibling_file(extension: &str) -> PathBuf {
+        let mut name = Self::config_file().into_os_string();
+        name.push(".");
+        name.push(extension);
+        PathBuf::from(name)
     }
 
-    pub fn load() -> Self {
+    /// Load the config file. A missing file yields the defaults; an
+    /// unreadable or invalid one is reported instead of being discarded.
+    pub fn load() -> Result<Self, ConfigError> {
         let path = Self::config_file();
-        if path.exists() {
-            if let Ok(content) = fs::read_to_string(&path) {
-                if let Ok(config) = toml::from_str(&content) {
-                    return config;
-                }
+        match fs::read_to_string(&path) {
+            Ok(content) => Self::parse(&path, &content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(ConfigError::io(&path, e)),
+        }
+    }
+
+    fn parse(path: &Path, content: &str) -> Result<Self, ConfigError> {
+        let mut table: toml::Table = toml::from_str(content)
+            .map_err(|e| ConfigError::parse(path, content, e))?;
+
+        // Files without a version predate versioning
+        let version = table
+            .get("version")
+            .and_then(|v| v.as_integer())
+            .unwrap_or(0)
+            .max(0) as usize;
+        if version >= MIGRATIONS.len() {
+            // Parse the text directly so errors keep their line and column
+            return toml::from_str(content).map_err(|e| ConfigError::parse(path, content, e));
+        }
+
+        for migrate in &MIGRATIONS[version..] {
+            migrate(&mut table);
+        }
+        table.insert("version".to_string(), toml::Value::Integer(CONFIG_VERSION.into()));
+
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| match toml::from_str::<Self>(content) {
+                // Re-parse the original text to point at the offending line
+                Err(located) => ConfigError::parse(path, content, located),
+                Ok(_) => ConfigError::Parse {
+                    path: path.to_path_buf(),
+                    location: None,
+                    message: e.message().trim().to_string(),
+                },
+            })
+    }
+
+    /// A config.toml with every setting at its default value and a comment
+    /// describing it, as printed by `rustyterm --print-default-config`.
+    pub fn default_template() -> String {
+        let defaults = Self::default();
+        format!(
+            r#"# RustyTerm configuration
+#
+# Every setting is optional: remove or comment out a line to use its
+# default value. Changes are applied to open windows when the file is saved.
+
+# Schema version of this file, used to migrate settings between releases.
+version = {version}
+
+# Color theme: a built-in theme (default, light, solarized_dark, dracula)
+# or the name of a theme in ~/.config/rustyterm/themes/.
+theme = "{theme}"
+
+# Font family and size in points.
+font_family = "{font_family}"
+font_size = {font_size}
+
+# Number of lines kept in the scrollback buffer.
+scrollback_lines = {scrollback_lines}
+
+# Size in pixels of new windows.
+window_width = {window_width}
+window_height = {window_height}
+
+# Open new windows maximized or fullscreen.
+window_maximized = {window_maximized}
+window_fullscreen = {window_fullscreen}
+
+# Size new windows to fit this many columns and rows of the terminal font
+# instead of the size in pixels above.
+# window_columns = 80
+# window_rows = 24
+
+# Save the size and state of a window to window.json in this directory
+# when it closes, and open new windows the same way instead of using the
+# settings above. Sizes given as columns and rows stay in columns and rows.
+remember_window_size = {remember_window_size}
+
+# Open new tabs and splits in the directory of the focused terminal, as
+# reported by the shell (OSC 7). Otherwise, or when the shell does not
+# report it, they start in the home directory. A profile's
+# working_directory always takes precedence.
+inherit_working_directory = {inherit_working_directory}
+
+# Load scripts into bash, zsh and fish that mark each prompt and command,
+# for jumping between prompts, copying a command's output and showing exit
+# statuses next to the scrollback. Applies to terminals opened afterwards.
+shell_integration = {shell_integration}
+
+# Closing a pane, tab or window asks for confirmation while a command
+# other than the shell is running in it, unless the command is listed here.
+confirm_close_ignore = {confirm_close_ignore:?}
+
+# Let scripts control rustyterm with JSON commands sent to the Unix socket
+# whose path is in $RUSTYTERM_LISTEN_ON. Only read at startup.
+remote_control = {remote_control}
+
+# Reopen the windows, tabs and splits of the last session at startup. The
+# session is saved when the last window closes or rustyterm quits.
+restore_session = {restore_session}
+
+# Profile opened by the new tab button and Ctrl+Shift+T.
+default_profile = "{default_profile}"
+
+# Named profiles, selectable from the drop-down next to the new tab button.
+# Every key except "name" is optional; theme and font settings fall back
+# to the global ones above. "env" adds variables to the environment,
+# "unset_env" removes them, and "login_shell" starts the command as a
+# login shell. "on_exit" decides what happens when the command exits:
+# "close" the pane, "hold" it open with the exit status, or offer to
+# "restart" the command.
+#
+# [[profiles]]
+# name = "Default"
+#
+# [[profiles]]
+# name = "Server"
+# command = "ssh"
+# args = ["admin@example.com"]
+# working_directory = "~/projects"
+# login_shell = false
+# unset_env = ["SSH_AUTH_SOCK"]
+# on_exit = "close"
+# theme = "dracula"
+# font_family = "Monospace"
+# font_size = 12
+# scrollback_lines = 10000
+#
+# [profiles.env]
+# EDITOR = "vim"
+
+# Keyboard shortcuts, mapping GTK accelerators to actions. An entry
+# replaces the default binding of its accelerator, and "unset" removes it.
+# Actions: new-window, new-tab, close-tab, next-tab, previous-tab,
+# goto-tab:N, last-used-tab, move-tab-left, move-tab-right, copy, paste,
+# search, search-all-tabs, previous-prompt, next-prompt, copy-last-output,
+# split-right, split-down, close-pane, focus-pane-left/right/up/down,
+# resize-pane-left/right/up/down, zoom-in, zoom-out, zoom-reset,
+# set-theme:NAME, import-theme, export-theme, quit, and send-text:TEXT,
+# which types TEXT into the terminal; "\\xNN" in TEXT sends the byte NN.
+#
+# [keybindings]
+# "<Ctrl><Shift>t" = "unset"
+# "<Ctrl>t" = "new-tab"
+# "<Alt>0" = "goto-tab:10"
+# "<Ctrl><Shift>F1" = "set-theme:dracula"
+# "<Ctrl><Alt>l" = "send-text:clear\\x0d"
+
+# Ctrl+click opens URLs and email addresses, and opens "path:line:col"
+# locations printed by compilers in $EDITOR. Each matcher adds a pattern,
+# checked before the built-in ones, with an "action": "open" it with the
+# default application, "edit" it in $EDITOR, "copy" it to the clipboard,
+# or run "command", where {{}} stands for the matched text. Single quotes
+# keep backslashes in the regex as they are.
+#
+# [[matchers]]
+# regex = '\bJIRA-[0-9]+\b'
+# action = "command"
+# command = "xdg-open https://jira.example.com/browse/{{}}"
+#
+# [[matchers]]
+# regex = '\b[0-9a-f]{{40}}\b'
+# action = "copy"
+"#,
+            version = defaults.version,
+            theme = defaults.theme,
+            font_family = defaults.font_family,
+            font_size = defaults.font_size,
+            scrollback_lines = defaults.scrollback_lines,
+            window_width = defaults.window_width,
+            window_height = defaults.window_height,
+            window_maximized = defaults.window_maximized,
+            window_fullscreen = defaults.window_fullscreen,
+            remember_window_size = defaults.remember_window_size,
+            inherit_working_directory = defaults.inherit_working_directory,
+            shell_integration = defaults.shell_integration,
+            confirm_close_ignore = defaults.confirm_close_ignore,
+            remote_control = defaults.remote_control,
+            restore_session = defaults.restore_session,
+            default_profile = defaults.default_profile,
+        )
+    }
+
+    /// Names of all profiles, starting with the default one. The default
+    /// profile exists even when the file does not define it.
+    pub fn profile_names(&self) -> Vec<String> {
+        let mut names = vec![self.default_profile.clone()];
+        for profile in &self.profiles {
+            if !names.contains(&profile.name) {
+                names.push(profile.name.clone());
             }
         }
-        Self::default()
+        names
+    }
+
+    /// Whether `name` is one of the [`Config::profile_names`].
+    pub fn has_profile(&self, name: &str) -> bool {
+        self.profile_names().iter().any(|profile| profile == name)
+    }
+
+    /// Look up a profile by name (the default profile when unknown) with
+    /// unset appearance fields filled in from the global settings.
+    pub fn profile(&self, name: &str) -> Profile {
+        let mut profile = self
+            .profiles
+            .iter()
+            .find(|p| p.name == name)
+            .or_else(|| self.profiles.iter().find(|p| p.name == self.default_profile))
+            .cloned()
+            .unwrap_or_else(|| Profile {
+                name: self.default_profile.clone(),
+                ..Profile::default()
+            });
+
+        profile.theme.get_or_insert_with(|| self.theme.clone());
+        profile.font_family.get_or_insert_with(|| self.font_family.clone());
+        profile.font_size.get_or_insert(self.font_size);
+        profile.scrollback_lines.get_or_insert(self.scrollback_lines);
+        profile
+    }
+
+    /// Names of fields that differ from `other` but only take effect in new
+    /// windows or tabs, or for `remote_control`, once rustyterm restarts.
+    pub fn restart_required_changes(&self, other: &Config) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.window_width != other.window_width {
+            fields.push("window_width");
+        }
+        if self.window_height != other.window_height {
+            fields.push("window_height");
+        }
+        if self.window_maximized != other.window_maximized {
+            fields.push("window_maximized");
+        }
+        if self.window_fullscreen != other.window_fullscreen {
+            fields.push("window_fullscreen");
+        }
+        if self.window_columns != other.window_columns || self.window_rows != other.window_rows {
+            fields.push("window_columns/rows");
+        }
+        let profiles_differ = self.profile_names() != other.profile_names()
+            || self.profile_names().iter().any(|name| {
+                self.profile(name).spawn_settings_differ(&other.profile(name))
+            });
+        if profiles_differ {
+            fields.push("profiles");
+        }
+        if self.shell_integration != other.shell_integration {
+            fields.push("shell_integration");
+        }
+        if self.remote_control != other.remote_control {
+            fields.push("remote_control");
+        }
+        fields
+    }
+
+    /// Set the top-level `key` of the config file to `value`, leaving the
+    /// rest of the file, comments included, as it is. The previous version
+    /// is kept in [`Config::backup_file`]. A file that does not parse is
+    /// never overwritten, so a half-edited config is not lost.
+    pub fn save_value(key: &str, value: impl Into<toml_edit::Value>) -> Result<(), ConfigError> {
+        Self::save_value_at(&Self::config_file(), &Self::backup_file(), key, value.into())
+    }
+
+    fn save_value_at(path: &Path, backup: &Path, key: &str, value: toml_edit::Value) -> Result<(), ConfigError> {
+        let mut document = match fs::read_to_string(path) {
+            Ok(existing) => {
+                Self::parse(path, &existing)?;
+                fs::copy(path, backup).map_err(|e| ConfigError::io(path, e))?;
+                existing.parse::<toml_edit::DocumentMut>().map_err(|e| ConfigError::Parse {
+                    path: path.to_path_buf(),
+                    location: e.span().map(|span| line_column(&existing, span.start)),
+                    message: e.message().trim().to_string(),
+                })?
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut document = toml_edit::DocumentMut::new();
+                document["version"] = toml_edit::value(i64::from(CONFIG_VERSION));
+                document
+            }
+            Err(e) => return Err(ConfigError::io(path, e)),
+        };
+        document[key] = toml_edit::Item::Value(value);
+
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| ConfigError::io(dir, e))?;
+        }
+        write_atomic(path, &document.to_string()).map_err(|e| ConfigError::io(path, e))
+    }
+}
+
+/// Replace the file at `path` with `content`. The text goes to a temporary
+/// file first, so a crash never leaves a truncated file behind. A symlink
+/// is followed, so the file it points to is replaced and the link stays,
+/// and the permissions of the replaced file are kept.
+pub fn write_atomic(path: &Path, content: &str) -> io::Result<()> {
+    let target = match fs::canonicalize(path) {
+        Ok(target) => target,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(e) => return Err(e),
+    };
+    let mut tmp_path = target.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    fs::write(&tmp_path, content)?;
+    let result = match fs::metadata(&target) {
+        Ok(metadata) => fs::set_permissions(&tmp_path, metadata.permissions()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    };
+    match result.and_then(|_| fs::rename(&tmp_path, &target)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp_path);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Result<Config, ConfigError> {
+        Config::parse(Path::new("config.toml"), content)
+    }
+
+    #[test]
+    fn missing_keys_take_defaults() {
+        let config = parse("version = 1\ntheme = \"dracula\"\n").unwrap();
+        assert_eq!(config, Config { theme: "dracula".to_string(), ..Config::default() });
+    }
+
+    #[test]
+    fn default_template_parses_to_defaults() {
+        assert_eq!(parse(&Config::default_template()).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn versionless_files_are_migrated() {
+        assert_eq!(MIGRATIONS.len(), CONFIG_VERSION as usize);
+        let config = parse("font_size = 14\n").unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.font_size, 14);
+    }
+
+    #[test]
+    fn future_versions_are_read_as_is() {
+        let config = parse("version = 99\ntheme = \"light\"\n").unwrap();
+        assert_eq!(config.version, 99);
+        assert_eq!(config.theme, "light");
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_line() {
+        match parse("theme = \"light\"\nfont_size = \"big\"\n") {
+            Err(ConfigError::Parse { location: Some((2, column)), .. }) => assert!(column > 1),
+            other => panic!("unexpected result {:?}", other),
+        }
+        match parse("version = 1\n\ntheme = \n") {
+            Err(ConfigError::Parse { location: Some((3, _)), .. }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn saving_a_value_keeps_the_rest_of_the_file() {
+        let dir = std::env::temp_dir().join(format!("rustyterm-test-save-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let (path, backup) = (dir.join("config.toml"), dir.join("config.toml.bak"));
+
+        Config::save_value_at(&path, &backup, "theme", "light".into()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "version = 1\ntheme = \"light\"\n");
+
+        let edited = "# My settings\nversion = 1\nfont_size = 14 # big\ntheme = \"light\"\n";
+        fs::write(&path, edited).unwrap();
+        Config::save_value_at(&path, &backup, "theme", "dracula".into()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# My settings\nversion = 1\nfont_size = 14 # big\ntheme = \"dracula\"\n"
+        );
+        assert_eq!(fs::read_to_string(&backup).unwrap(), edited);
+
+        fs::write(&path, "theme = \n").unwrap();
+        assert!(Config::save_value_at(&path, &backup, "theme", "light".into()).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "theme = \n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn startup_only_changes_are_reported() {
+        let config = Config::default();
+        let changed = Config {
+            theme: "light".to_string(),
+            window_width: config.window_width + 1,
+            shell_integration: !config.shell_integration,
+            remote_control: !config.remote_control,
+            ..Config::default()
+        };
+        assert_eq!(
+            changed.restart_required_changes(&config),
+            ["window_width", "shell_integration", "remote_control"]
+        );
+        assert!(config.restart_required_changes(&Config::default()).is_empty());
+    }
+
+    #[test]
+    fn atomic_writes_replace_the_file() {
+        let path = std::env::temp_dir().join(format!("rustyterm-test-{}.toml", std::process::id()));
+        write_atomic(&path, "theme = \"light\"\n").unwrap();
+        write_atomic(&path, "theme = \"dracula\"\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "theme = \"dracula\"\n");
+        assert!(!path.with_extension("toml.tmp").exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn atomic_writes_follow_symlinks_and_keep_permissions() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = std::env::temp_dir().join(format!("rustyterm-test-link-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("dotfiles")).unwrap();
+        let target = dir.join("dotfiles/config.toml");
+        let link = dir.join("config.toml");
+        fs::write(&target, "theme = \"light\"\n").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        symlink(&target, &link).unwrap();
+
+        write_atomic(&link, "theme = \"dracula\"\n").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "theme = \"dracula\"\n");
+        assert_eq!(fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(!dir.join("dotfiles/config.toml.tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
     }
 
-    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let dir = Self::config_dir();
-        fs::create_dir_all(&dir)?;
-        let content = toml::to_string_pretty(self)?;
-        fs::write(Self::config_file(), content)?;
-        Ok(())
+    #[test]
+    fn line_column_counts_characters() {
+        assert_eq!(line_column("abc", 0), (1, 1));
+        assert_eq!(line_column("abc\ndef", 5), (2, 2));
+        assert_eq!(line_column("é = 1\nx", 3), (1, 3));
+        assert_eq!(line_column("ab", 10), (1, 3));
     }
 }
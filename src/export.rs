@@ -0,0 +1,378 @@
+//! Color scheme export to other terminal emulators.
+//!
+//! The counterpart of [`crate::import`]: a [`Theme`] is written as
+//! Alacritty TOML, kitty conf, Xresources, a Windows Terminal JSON scheme
+//! or a shell script that sets the colors through OSC 4/10/11/12.
+
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+use crate::theme::{hex_color, Theme};
+
+/// Target format of an exported color scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Alacritty,
+    Kitty,
+    Xresources,
+    WindowsTerminal,
+    Shell,
+}
+
+impl ExportFormat {
+    /// Names accepted on the command line.
+    pub const NAMES: [&'static str; 5] = ["alacritty", "kitty", "xresources", "windows-terminal", "shell"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "alacritty" => Some(ExportFormat::Alacritty),
+            "kitty" => Some(ExportFormat::Kitty),
+            "xresources" => Some(ExportFormat::Xresources),
+            "windows-terminal" => Some(ExportFormat::WindowsTerminal),
+            "shell" => Some(ExportFormat::Shell),
+            _ => None,
+        }
+    }
+
+    /// Pick the format from a file name chosen by the user.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_string_lossy().to_lowercase();
+        if file_name.contains("xresources") {
+            return Some(ExportFormat::Xresources);
+        }
+        match path.extension()?.to_string_lossy().to_lowercase().as_str() {
+            "toml" => Some(ExportFormat::Alacritty),
+            "conf" => Some(ExportFormat::Kitty),
+            "json" => Some(ExportFormat::WindowsTerminal),
+            "sh" => Some(ExportFormat::Shell),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ExportError(String);
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+/// Serialize a theme into the given format.
+pub fn export_theme(theme: &Theme, format: ExportFormat) -> Result<String, ExportError> {
+    let colors = Colors::new(theme);
+    match format {
+        ExportFormat::Alacritty => export_alacritty(&colors),
+        ExportFormat::Kitty => Ok(export_kitty(&colors)),
+        ExportFormat::Xresources => Ok(export_xresources(&colors)),
+        ExportFormat::WindowsTerminal => export_windows_terminal(&colors),
+        ExportFormat::Shell => Ok(export_shell(&colors)),
+    }
+}
+
+/// Theme colors normalized to `#rrggbb` with a full 16 color palette.
+struct Colors<'a> {
+    name: &'a str,
+    background: String,
+    foreground: String,
+    cursor: String,
+    palette: Vec<String>,
+}
+
+impl<'a> Colors<'a> {
+    fn new(theme: &'a Theme) -> Self {
+        // 8 color palettes reuse the normal colors for the bright ones
+        let palette = (0..16)
+            .map(|i| match theme.palette.len() {
+                0 => hex_color(&theme.foreground),
+                len if i < len => hex_color(&theme.palette[i]),
+                len => hex_color(&theme.palette[i % len]),
+            })
+            .collect();
+
+        Self {
+            name: &theme.name,
+            background: hex_color(&theme.background),
+            foreground: hex_color(&theme.foreground),
+            cursor: hex_color(&theme.cursor),
+            palette,
+        }
+    }
+}
+
+// Alacritty
+
+#[derive(Serialize)]
+struct AlacrittyConfig<'a> {
+    colors: AlacrittyColors<'a>,
+}
+
+#[derive(Serialize)]
+struct AlacrittyColors<'a> {
+    primary: AlacrittyPrimary<'a>,
+    cursor: AlacrittyCursor<'a>,
+    normal: AlacrittyAnsi<'a>,
+    bright: AlacrittyAnsi<'a>,
+}
+
+#[derive(Serialize)]
+struct AlacrittyPrimary<'a> {
+    background: &'a str,
+    foreground: &'a str,
+}
+
+#[derive(Serialize)]
+struct AlacrittyCursor<'a> {
+    text: &'a str,
+    cursor: &'a str,
+}
+
+#[derive(Serialize)]
+struct AlacrittyAnsi<'a> {
+    black: &'a str,
+    red: &'a str,
+    green: &'a str,
+    yellow: &'a str,
+    blue: &'a str,
+    magenta: &'a str,
+    cyan: &'a str,
+    white: &'a str,
+}
+
+impl<'a> AlacrittyAnsi<'a> {
+    fn new(colors: &'a [String]) -> Self {
+        Self {
+            black: &colors[0],
+            red: &colors[1],
+            green: &colors[2],
+            yellow: &colors[3],
+            blue: &colors[4],
+            magenta: &colors[5],
+            cyan: &colors[6],
+            white: &colors[7],
+        }
+    }
+}
+
+fn export_alacritty(colors: &Colors) -> Result<String, ExportError> {
+    let config = AlacrittyConfig {
+        colors: AlacrittyColors {
+            primary: AlacrittyPrimary {
+                background: &colors.background,
+                foreground: &colors.foreground,
+            },
+            cursor: AlacrittyCursor {
+                text: &colors.background,
+                cursor: &colors.cursor,
+            },
+            normal: AlacrittyAnsi::new(&colors.palette[..8]),
+            bright: AlacrittyAnsi::new(&colors.palette[8..]),
+        },
+    };
+
+    let body = toml::to_string_pretty(&config).map_err(|e| ExportError(e.to_string()))?;
+    Ok(format!("# {} (exported from rustyterm)\n\n{}", colors.name, body))
+}
+
+// kitty
+
+fn export_kitty(colors: &Colors) -> String {
+    let mut out = format!("## name: {}\n\n", colors.name);
+    out.push_str(&format!("background {}\n", colors.background));
+    out.push_str(&format!("foreground {}\n", colors.foreground));
+    out.push_str(&format!("cursor {}\n", colors.cursor));
+    out.push_str(&format!("cursor_text_color {}\n\n", colors.background));
+
+    for (i, color) in colors.palette.iter().enumerate() {
+        out.push_str(&format!("color{} {}\n", i, color));
+    }
+    out
+}
+
+// Xresources
+
+fn export_xresources(colors: &Colors) -> String {
+    let mut out = format!("! {} (exported from rustyterm)\n\n", colors.name);
+    out.push_str(&format!("*.background: {}\n", colors.background));
+    out.push_str(&format!("*.foreground: {}\n", colors.foreground));
+    out.push_str(&format!("*.cursorColor: {}\n\n", colors.cursor));
+
+    for (i, color) in colors.palette.iter().enumerate() {
+        out.push_str(&format!("*.color{}: {}\n", i, color));
+    }
+    out
+}
+
+// Windows Terminal
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WindowsTerminalScheme<'a> {
+    name: &'a str,
+    background: &'a str,
+    foreground: &'a str,
+    cursor_color: &'a str,
+    selection_background: &'a str,
+    black: &'a str,
+    red: &'a str,
+    green: &'a str,
+    yellow: &'a str,
+    blue: &'a str,
+    purple: &'a str,
+    cyan: &'a str,
+    white: &'a str,
+    bright_black: &'a str,
+    bright_red: &'a str,
+    bright_green: &'a str,
+    bright_yellow: &'a str,
+    bright_blue: &'a str,
+    bright_purple: &'a str,
+    bright_cyan: &'a str,
+    bright_white: &'a str,
+}
+
+fn export_windows_terminal(colors: &Colors) -> Result<String, ExportError> {
+    let p = &colors.palette;
+    let scheme = WindowsTerminalScheme {
+        name: colors.name,
+        background: &colors.background,
+        foreground: &colors.foreground,
+        cursor_color: &colors.cursor,
+        selection_background: &p[8],
+        black: &p[0],
+        red: &p[1],
+        green: &p[2],
+        yellow: &p[3],
+        blue: &p[4],
+        purple: &p[5],
+        cyan: &p[6],
+        white: &p[7],
+        bright_black: &p[8],
+        bright_red: &p[9],
+        bright_green: &p[10],
+        bright_yellow: &p[11],
+        bright_blue: &p[12],
+        bright_purple: &p[13],
+        bright_cyan: &p[14],
+        bright_white: &p[15],
+    };
+
+    let mut json = serde_json::to_string_pretty(&scheme).map_err(|e| ExportError(e.to_string()))?;
+    json.push('\n');
+    Ok(json)
+}
+
+// Shell script
+
+/// Convert `#rrggbb` to the `rgb:rr/gg/bb` form used in OSC color sequences.
+fn osc_color(hex: &str) -> String {
+    let hex = hex.trim_start_matches('#');
+    format!("rgb:{}/{}/{}", &hex[0..2], &hex[2..4], &hex[4..6])
+}
+
+fn export_shell(colors: &Colors) -> String {
+    let mut out = String::from("#!/bin/sh\n");
+    out.push_str(&format!("# {} (exported from rustyterm)\n", colors.name));
+    out.push_str("# Sets the terminal colors with OSC 4/10/11/12 escape sequences.\n\n");
+    out.push_str("if [ -n \"$TMUX\" ]; then\n");
+    out.push_str("    # tmux passthrough: wrap in DCS and double the inner escape\n");
+    out.push_str("    osc() { printf '\\033Ptmux;\\033\\033]%s\\007\\033\\\\' \"$1\"; }\n");
+    out.push_str("else\n");
+    out.push_str("    osc() { printf '\\033]%s\\007' \"$1\"; }\n");
+    out.push_str("fi\n\n");
+
+    for (i, color) in colors.palette.iter().enumerate() {
+        out.push_str(&format!("osc \"4;{};{}\"\n", i, osc_color(color)));
+    }
+    out.push_str(&format!("osc \"10;{}\"\n", osc_color(&colors.foreground)));
+    out.push_str(&format!("osc \"11;{}\"\n", osc_color(&colors.background)));
+    out.push_str(&format!("osc \"12;{}\"\n", osc_color(&colors.cursor)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{import_str, Format};
+
+    fn sample_theme() -> Theme {
+        Theme {
+            name: "Sample".to_string(),
+            background: "#1d1f21".to_string(),
+            foreground: "#c5c8c6".to_string(),
+            cursor: "#aeafad".to_string(),
+            palette: vec![
+                "#282a2e", "#a54242", "#8c9440", "#de935f", "#5f819d", "#85678f", "#5e8d87", "#707880",
+                "#373b41", "#cc6666", "#b5bd68", "#f0c674", "#81a2be", "#b294bb", "#8abeb7", "#ffffff",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        }
+    }
+
+    fn assert_round_trip(export: ExportFormat, import: Format) {
+        let theme = sample_theme();
+        let exported = export_theme(&theme, export).unwrap();
+        let imported = import_str(&exported, import, "Sample").unwrap();
+
+        assert_eq!(imported.name, theme.name);
+        assert_eq!(imported.background, theme.background);
+        assert_eq!(imported.foreground, theme.foreground);
+        assert_eq!(imported.cursor, theme.cursor);
+        assert_eq!(imported.palette, theme.palette);
+    }
+
+    #[test]
+    fn alacritty_round_trip() {
+        assert_round_trip(ExportFormat::Alacritty, Format::AlacrittyToml);
+    }
+
+    #[test]
+    fn kitty_round_trip() {
+        assert_round_trip(ExportFormat::Kitty, Format::Kitty);
+    }
+
+    #[test]
+    fn windows_terminal_round_trip() {
+        assert_round_trip(ExportFormat::WindowsTerminal, Format::WindowsTerminal);
+    }
+
+    #[test]
+    fn xresources_lists_all_colors() {
+        let out = export_theme(&sample_theme(), ExportFormat::Xresources).unwrap();
+        assert!(out.contains("*.background: #1d1f21\n"));
+        assert!(out.contains("*.cursorColor: #aeafad\n"));
+        assert!(out.contains("*.color15: #ffffff\n"));
+    }
+
+    #[test]
+    fn shell_script_sets_osc_colors() {
+        let out = export_theme(&sample_theme(), ExportFormat::Shell).unwrap();
+        assert!(out.starts_with("#!/bin/sh\n"));
+        assert!(out.contains("osc \"4;1;rgb:a5/42/42\"\n"));
+        assert!(out.contains("osc \"10;rgb:c5/c8/c6\"\n"));
+        assert!(out.contains("osc \"11;rgb:1d/1f/21\"\n"));
+        assert!(out.contains("osc \"12;rgb:ae/af/ad\"\n"));
+    }
+
+    #[test]
+    fn short_palette_is_extended() {
+        let mut theme = sample_theme();
+        theme.palette.truncate(8);
+        let out = export_theme(&theme, ExportFormat::Kitty).unwrap();
+        assert!(out.contains("color8 #282a2e\n"));
+        assert!(out.contains("color15 #707880\n"));
+    }
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(ExportFormat::from_path(Path::new("x.toml")), Some(ExportFormat::Alacritty));
+        assert_eq!(ExportFormat::from_path(Path::new(".Xresources")), Some(ExportFormat::Xresources));
+        assert_eq!(ExportFormat::from_path(Path::new("x.png")), None);
+    }
+}
@@ -0,0 +1,272 @@
+//! Search across every tab.
+//!
+//! A dialog runs one query against the scrollback of every terminal of
+//! every window and lists the matching lines grouped by tab. Activating a
+//! result shows its tab and scrolls the terminal to it.
+//!
+//! A search reads the whole scrollback of every pane at once, on the main
+//! thread, so very long scrollbacks across many tabs make the dialog pause
+//! while it runs. Searching waits for a pause in typing to keep that rare,
+//! and stops at `MAX_RESULTS`, but reading the text itself is not split up.
+
+use gtk4::prelude::*;
+use gtk4::{
+    Align, ApplicationWindow, Box, Label, ListBox, ListBoxRow, Orientation, PolicyType,
+    ScrolledWindow, SearchEntry, SelectionMode, Window,
+};
+use vte4::TerminalExt;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use crate::pane::Pane;
+use crate::search::{describe_count, mark_matches, SearchOptions, SearchQuery, MATCH_MARKER};
+use crate::terminal::rows_of;
+use crate::window::RustyTermWindow;
+
+/// Lines shown above and below each matching line.
+const CONTEXT_LINES: usize = 1;
+
+/// Results listed at most, so huge scrollbacks keep the dialog responsive.
+const MAX_RESULTS: usize = 500;
+
+/// Pause in typing after which the search runs, as every search reads the
+/// scrollback of every terminal. Enter searches right away.
+const SEARCH_DELAY_MS: u32 = 500;
+
+/// Where a result was found.
+struct Target {
+    window: Weak<RustyTermWindow>,
+    tab_id: u32,
+    pane: Weak<Pane>,
+    /// Terminal row of the match, counted from the top of the scrollback
+    row: usize,
+}
+
+/// Open the search dialog over `parent`.
+pub fn show(parent: &ApplicationWindow, windows: &Rc<RefCell<Vec<Rc<RustyTermWindow>>>>) {
+    let entry = SearchEntry::new();
+    entry.set_placeholder_text(Some("Search all tabs"));
+    entry.set_hexpand(true);
+    entry.set_search_delay(SEARCH_DELAY_MS);
+    let options = Rc::new(SearchOptions::new());
+
+    let controls = Box::new(Orientation::Horizontal, 4);
+    controls.append(&entry);
+    controls.append(&options.case_sensitive);
+    controls.append(&options.whole_word);
+    controls.append(&options.regex);
+
+    let status = Label::new(None);
+    status.set_xalign(0.0);
+    status.set_wrap(true);
+
+    let list = ListBox::new();
+    list.set_selection_mode(SelectionMode::None);
+    list.set_activate_on_single_click(true);
+    let scrolled = ScrolledWindow::new();
+    scrolled.set_policy(PolicyType::Never, PolicyType::Automatic);
+    scrolled.set_vexpand(true);
+    scrolled.set_child(Some(&list));
+
+    let content = Box::new(Orientation::Vertical, 6);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.append(&controls);
+    content.append(&status);
+    content.append(&scrolled);
+
+    let dialog = Window::builder()
+        .title("Search All Tabs")
+        .transient_for(parent)
+        .destroy_with_parent(true)
+        .default_width(720)
+        .default_height(520)
+        .child(&content)
+        .build();
+
+    // Target of each row of the list, `None` for tab headers
+    let targets: Rc<RefCell<Vec<Option<Target>>>> = Rc::new(RefCell::new(Vec::new()));
+    // Query the results are for, so Enter after the delay does not search again
+    let last_query: Rc<RefCell<Option<SearchQuery>>> = Rc::new(RefCell::new(None));
+
+    let search = {
+        let entry = entry.clone();
+        let options = options.clone();
+        let status = status.clone();
+        let list = list.clone();
+        let targets = targets.clone();
+        let windows = windows.clone();
+        Rc::new(move || {
+            let query = options.query(&entry.text());
+            if last_query.borrow().as_ref() == Some(&query) {
+                return;
+            }
+            run(&query, &windows, &list, &status, &targets);
+            last_query.replace(Some(query));
+        })
+    };
+    let on_toggle = search.clone();
+    options.connect_changed(move || on_toggle());
+    let on_activate = search.clone();
+    entry.connect_activate(move |_| on_activate());
+    entry.connect_search_changed(move |_| search());
+
+    let dialog_clone = dialog.clone();
+    entry.connect_stop_search(move |_| dialog_clone.close());
+
+    list.connect_row_activated(move |_, row| {
+        let index = row.index();
+        if index < 0 {
+            return;
+        }
+        if let Some(Some(target)) = targets.borrow().get(index as usize) {
+            reveal(target);
+        }
+    });
+
+    dialog.present();
+    entry.grab_focus();
+}
+
+/// Replace the results in `list` with the lines matching `query`.
+fn run(
+    query: &SearchQuery,
+    windows: &Rc<RefCell<Vec<Rc<RustyTermWindow>>>>,
+    list: &ListBox,
+    status: &Label,
+    targets: &Rc<RefCell<Vec<Option<Target>>>>,
+) {
+    while let Some(row) = list.first_child() {
+        list.remove(&row);
+    }
+    targets.borrow_mut().clear();
+    status.remove_css_class("error");
+    status.set_text("");
+
+    let regex = match query.compile() {
+        Ok(Some(regex)) => regex,
+        Ok(None) => return,
+        Err(e) => {
+            status.add_css_class("error");
+            status.set_text(e.message());
+            return;
+        }
+    };
+
+    let windows: Vec<Rc<RustyTermWindow>> = windows.borrow().clone();
+    let (mut total, mut tabs_with_matches) = (0, 0);
+    // Whether a match was found beyond the ones listed
+    let mut truncated = false;
+    'scan: for (window_number, window) in windows.iter().enumerate() {
+        for tab in window.tabs() {
+            let tab = tab.borrow();
+            let mut header_added = false;
+            for pane in tab.panes() {
+                let Ok(text) = pane.terminal.text(true) else {
+                    continue;
+                };
+                let Some(marked) = mark_matches(&regex, &text) else {
+                    continue;
+                };
+                let lines: Vec<&str> = marked.lines().collect();
+                let columns = pane.terminal.widget().column_count().max(1) as usize;
+                let mut row = 0;
+                for (index, line) in lines.iter().enumerate() {
+                    let line_row = row;
+                    row += rows_of(line, columns);
+                    if !line.contains(MATCH_MARKER) {
+                        continue;
+                    }
+                    if total == MAX_RESULTS {
+                        truncated = true;
+                        break 'scan;
+                    }
+                    total += 1;
+                    if !header_added {
+                        header_added = true;
+                        tabs_with_matches += 1;
+                        let title = format!("{} — window {}", tab.title_label.text(), window_number + 1);
+                        list.append(&header_row(&title));
+                        targets.borrow_mut().push(None);
+                    }
+                    list.append(&result_row(&lines, index));
+                    targets.borrow_mut().push(Some(Target {
+                        window: Rc::downgrade(window),
+                        tab_id: tab.id,
+                        pane: Rc::downgrade(pane),
+                        row: line_row,
+                    }));
+                }
+            }
+        }
+    }
+
+    status.set_text(&if truncated {
+        format!("Showing the first {} matches", MAX_RESULTS)
+    } else if tabs_with_matches == 1 {
+        format!("{} in 1 tab", describe_count(total))
+    } else if total > 0 {
+        format!("{} in {} tabs", describe_count(total), tabs_with_matches)
+    } else {
+        describe_count(0)
+    });
+}
+
+fn header_row(title: &str) -> ListBoxRow {
+    let label = Label::new(Some(title));
+    label.set_xalign(0.0);
+    label.add_css_class("heading");
+    label.set_margin_top(8);
+    label.set_margin_start(6);
+
+    let row = ListBoxRow::new();
+    row.set_activatable(false);
+    row.set_selectable(false);
+    row.set_child(Some(&label));
+    row
+}
+
+/// Row showing the matching line in bold between its context lines.
+fn result_row(lines: &[&str], index: usize) -> ListBoxRow {
+    let start = index.saturating_sub(CONTEXT_LINES);
+    let end = (index + CONTEXT_LINES + 1).min(lines.len());
+    let markup: Vec<String> = (start..end)
+        .map(|i| {
+            let line = glib::markup_escape_text(&lines[i].replace(MATCH_MARKER, ""));
+            if i == index {
+                format!("<b>{}</b>", line)
+            } else {
+                format!("<span alpha=\"60%\">{}</span>", line)
+            }
+        })
+        .collect();
+
+    let label = Label::new(None);
+    label.set_markup(&markup.join("\n"));
+    label.set_xalign(0.0);
+    label.set_halign(Align::Fill);
+    label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+    label.add_css_class("monospace");
+    label.set_margin_top(4);
+    label.set_margin_bottom(4);
+    label.set_margin_start(12);
+
+    let row = ListBoxRow::new();
+    row.set_child(Some(&label));
+    row
+}
+
+/// Show the tab holding `target` and scroll its terminal to the match.
+fn reveal(target: &Target) {
+    let (Some(window), Some(pane)) = (target.window.upgrade(), target.pane.upgrade()) else {
+        return;
+    };
+    if let Some(index) = window.tabs().iter().position(|tab| tab.borrow().id == target.tab_id) {
+        window.focus_tab(index);
+    }
+    window.present();
+    pane.terminal.widget().grab_focus();
+    pane.terminal.scroll_to_row(target.row);
+}
@@ -0,0 +1,153 @@
+//! OSC 8 hyperlinks.
+//!
+//! Programs such as `ls --hyperlink`, `gcc` and `systemctl` attach a URI to
+//! the text they print. Ctrl+click opens it with the default application,
+//! after asking first when the link may not lead where its text suggests.
+
+use gtk4::prelude::*;
+use gtk4::{gio, ButtonsType, DialogFlags, MessageDialog, MessageType, ResponseType, Widget, Window};
+
+/// Open `uri` with the default application for its scheme.
+pub fn open_uri(widget: &impl IsA<Widget>, uri: &str) -> Result<(), String> {
+    let context = widget.display().app_launch_context();
+    gio::AppInfo::launch_default_for_uri(uri, Some(&context))
+        .map_err(|e| format!("Failed to open {}: {}", uri, e.message()))
+}
+
+/// Open the target of a hyperlink whose visible `text` is known when it
+/// looks like a URL. Links that fail [`warning`] are confirmed first.
+/// `on_error` reports a link that could not be opened.
+pub fn open<F>(widget: &impl IsA<Widget>, uri: &str, text: Option<&str>, on_error: F)
+where
+    F: Fn(&str) + 'static,
+{
+    let Some(warning) = warning(uri, text, &glib::host_name()) else {
+        if let Err(e) = open_uri(widget, uri) {
+            on_error(&e);
+        }
+        return;
+    };
+
+    let parent = widget.root().and_downcast::<Window>();
+    let dialog = MessageDialog::new(
+        parent.as_ref(),
+        DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+        MessageType::Warning,
+        ButtonsType::None,
+        "Open this link?",
+    );
+    dialog.set_secondary_text(Some(&format!("{}\n\n{}", warning, uri)));
+    dialog.add_button("_Cancel", ResponseType::Cancel);
+    dialog.add_button("_Open Link", ResponseType::Accept);
+    dialog.set_default_response(ResponseType::Cancel);
+
+    let widget = widget.clone().upcast::<Widget>();
+    let uri = uri.to_string();
+    dialog.connect_response(move |dialog, response| {
+        dialog.close();
+        if response == ResponseType::Accept
+            && let Err(e) = open_uri(&widget, &uri)
+        {
+            on_error(&e);
+        }
+    });
+    dialog.present();
+}
+
+/// Why `uri` should be confirmed before opening, if it should: schemes
+/// other than web pages and files, files on other hosts, and web links
+/// whose `text` does not show their host.
+fn warning(uri: &str, text: Option<&str>, hostname: &str) -> Option<String> {
+    let Some((scheme, host)) = scheme_and_host(uri) else {
+        return Some("This link is not a valid URI.".to_string());
+    };
+    match scheme.as_str() {
+        "http" | "https" => {
+            let text_host = text.and_then(host_of_text);
+            match text_host {
+                Some(text_host) if text_host == host => None,
+                Some(text_host) => Some(format!(
+                    "The link shows “{}” but leads to “{}”.",
+                    text_host, host
+                )),
+                None => Some(format!("The link leads to “{}”, which its text does not show.", host)),
+            }
+        }
+        "file" => {
+            let is_local = host.is_empty() || host == "localhost" || host.eq_ignore_ascii_case(hostname);
+            (!is_local).then(|| format!("This link points to a file on “{}”, not on this computer.", host))
+        }
+        _ => Some(format!(
+            "This link uses the “{}” scheme, which may start another application.",
+            scheme
+        )),
+    }
+}
+
+/// Lowercase scheme and host of a URI. The host is empty for URIs without
+/// an authority, such as `mailto:`.
+fn scheme_and_host(uri: &str) -> Option<(String, String)> {
+    let (scheme, rest) = uri.split_once(':')?;
+    let valid_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+    if !valid_scheme {
+        return None;
+    }
+    let host = match rest.strip_prefix("//") {
+        Some(authority) => host_of_authority(authority),
+        None => String::new(),
+    };
+    Some((scheme.to_ascii_lowercase(), host))
+}
+
+/// Host named by link text that looks like a URL or a domain name.
+fn host_of_text(text: &str) -> Option<String> {
+    let text = text.trim();
+    let authority = match text.split_once("://") {
+        Some((_, rest)) => rest,
+        None => text,
+    };
+    let host = host_of_authority(authority);
+    (host.contains('.') && !host.contains(char::is_whitespace)).then_some(host)
+}
+
+/// Host of `authority[/path...]`, without user name or port.
+fn host_of_authority(authority: &str) -> String {
+    let end = authority.find(['/', '?', '#']).unwrap_or(authority.len());
+    let authority = &authority[..end];
+    let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+    host.to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_showing_their_host_open_directly() {
+        assert_eq!(warning("https://example.com/a", Some("https://example.com/a"), "pc"), None);
+        assert_eq!(warning("https://Example.com:8080/a", Some("example.com"), "pc"), None);
+        assert_eq!(warning("file:///home/me/a.txt", None, "pc"), None);
+        assert_eq!(warning("file://pc/home/me/a.txt", None, "pc"), None);
+    }
+
+    #[test]
+    fn misleading_or_unusual_links_are_confirmed() {
+        assert!(warning("https://evil.example/", Some("https://bank.example/"), "pc").is_some());
+        assert!(warning("https://gcc.gnu.org/onlinedocs", None, "pc").is_some());
+        assert!(warning("file://server/etc/passwd", None, "pc").is_some());
+        assert!(warning("ssh://host", None, "pc").is_some());
+        assert!(warning("not a uri", None, "pc").is_some());
+    }
+
+    #[test]
+    fn hosts_ignore_user_and_port() {
+        assert_eq!(scheme_and_host("HTTPS://user@Host.example:443/x"), Some(("https".into(), "host.example".into())));
+        assert_eq!(scheme_and_host("mailto:a@b.example"), Some(("mailto".into(), String::new())));
+        assert_eq!(host_of_text("click here"), None);
+    }
+}
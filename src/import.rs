@@ -0,0 +1,672 @@
+//! Color scheme import from other terminal emulators.
+//!
+//! Supported formats are iTerm2 `.itermcolors` (plist XML), Alacritty
+//! `colors` (TOML or YAML), kitty `.conf` files, Windows Terminal JSON
+//! scheme objects and base16 YAML schemes. Every format is turned into a
+//! [`Theme`] with a 16 color palette.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::theme::{themes_dir, Theme};
+
+/// ANSI color names in palette order, as used by Alacritty.
+const ANSI_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+/// Source format of a color scheme file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    ITerm,
+    AlacrittyToml,
+    AlacrittyYaml,
+    Kitty,
+    WindowsTerminal,
+    Base16,
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(std::io::Error),
+    UnknownFormat,
+    Parse(String),
+    MissingColor(String),
+    InvalidColor(String),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(e) => write!(f, "{}", e),
+            ImportError::UnknownFormat => write!(f, "unrecognized color scheme format"),
+            ImportError::Parse(msg) => write!(f, "parse error: {}", msg),
+            ImportError::MissingColor(key) => write!(f, "missing color \"{}\"", key),
+            ImportError::InvalidColor(value) => write!(f, "invalid color \"{}\"", value),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<std::io::Error> for ImportError {
+    fn from(e: std::io::Error) -> Self {
+        ImportError::Io(e)
+    }
+}
+
+/// Guess the format from the file extension, looking at the content
+/// when the extension is ambiguous.
+pub fn detect_format(path: &Path, content: &str) -> Option<Format> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    match ext.as_str() {
+        "itermcolors" => Some(Format::ITerm),
+        "json" => Some(Format::WindowsTerminal),
+        "conf" => Some(Format::Kitty),
+        "toml" => Some(Format::AlacrittyToml),
+        "yml" | "yaml" => {
+            let map = parse_yaml(content);
+            if map.keys().any(|k| k.ends_with("base00")) {
+                Some(Format::Base16)
+            } else {
+                Some(Format::AlacrittyYaml)
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Import a color scheme file, naming the theme after the file unless the
+/// format carries its own name.
+pub fn import_file(path: &Path) -> Result<Theme, ImportError> {
+    let content = fs::read_to_string(path)?;
+    let format = detect_format(path, &content).ok_or(ImportError::UnknownFormat)?;
+    let fallback_name = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "imported".to_string());
+
+    import_str(&content, format, &fallback_name)
+}
+
+/// Import a color scheme from a string in the given format.
+pub fn import_str(content: &str, format: Format, fallback_name: &str) -> Result<Theme, ImportError> {
+    match format {
+        Format::ITerm => import_iterm(content, fallback_name),
+        Format::AlacrittyToml => {
+            let value: toml::Value = toml::from_str(content)
+                .map_err(|e| ImportError::Parse(e.to_string()))?;
+            let mut map = HashMap::new();
+            flatten_toml("", &value, &mut map);
+            import_alacritty(&map, fallback_name)
+        }
+        Format::AlacrittyYaml => import_alacritty(&parse_yaml(content), fallback_name),
+        Format::Kitty => import_kitty(content, fallback_name),
+        Format::WindowsTerminal => import_windows_terminal(content, fallback_name),
+        Format::Base16 => import_base16(&parse_yaml(content), fallback_name),
+    }
+}
+
+/// Write an imported theme to the user themes directory and return its path.
+/// A theme file already there is kept, and the theme is renamed instead.
+pub fn save_theme(theme: &mut Theme) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    save_theme_in(&themes_dir(), theme)
+}
+
+/// Write `theme` to a new file of `dir`. When `<name>.toml` exists, the
+/// theme becomes "name 2" in `<name>-2.toml`, or the next free number.
+fn save_theme_in(dir: &Path, theme: &mut Theme) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir)?;
+
+    let file_name: String = theme
+        .name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let name = theme.name.clone();
+    let mut number = 1;
+    loop {
+        let path = if number == 1 {
+            dir.join(format!("{}.toml", file_name))
+        } else {
+            theme.name = format!("{} {}", name, number);
+            dir.join(format!("{}-{}.toml", file_name, number))
+        };
+        match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(toml::to_string_pretty(theme)?.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => number += 1,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Normalize `#rrggbb`, `0xrrggbb` or bare `rrggbb` to lowercase `#rrggbb`.
+fn normalize_color(value: &str) -> Result<String, ImportError> {
+    let value = value.trim();
+    let hex = value
+        .strip_prefix('#')
+        .or_else(|| value.strip_prefix("0x"))
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+
+    if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(format!("#{}", hex.to_lowercase()))
+    } else {
+        Err(ImportError::InvalidColor(value.to_string()))
+    }
+}
+
+fn rgb_to_hex(r: f64, g: f64, b: f64) -> String {
+    let channel = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(r), channel(g), channel(b))
+}
+
+// iTerm2
+
+#[derive(Debug)]
+enum PlistValue {
+    Dict(Vec<(String, PlistValue)>),
+    Real(f64),
+    Other,
+}
+
+impl PlistValue {
+    fn get(&self, key: &str) -> Option<&PlistValue> {
+        match self {
+            PlistValue::Dict(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_real(&self) -> Option<f64> {
+        match self {
+            PlistValue::Real(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum XmlToken {
+    Open(String),
+    Close(String),
+    Empty,
+    Text(String),
+}
+
+fn tokenize_xml(content: &str) -> Result<Vec<XmlToken>, ImportError> {
+    let mut tokens = Vec::new();
+    let mut rest = content;
+
+    while let Some(start) = rest.find('<') {
+        let text = rest[..start].trim();
+        if !text.is_empty() {
+            tokens.push(XmlToken::Text(decode_entities(text)));
+        }
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("<!--") {
+            let end = after.find("-->").ok_or_else(|| ImportError::Parse("unterminated comment".into()))?;
+            rest = &after[end + 3..];
+            continue;
+        }
+
+        let end = rest.find('>').ok_or_else(|| ImportError::Parse("unterminated tag".into()))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            tokens.push(XmlToken::Close(name.trim().to_string()));
+        } else if tag.ends_with('/') {
+            tokens.push(XmlToken::Empty);
+        } else {
+            let name = tag.split_whitespace().next().unwrap_or_default();
+            tokens.push(XmlToken::Open(name.to_string()));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn parse_plist_value(tokens: &[XmlToken], pos: &mut usize) -> Result<PlistValue, ImportError> {
+    let unexpected = || ImportError::Parse("unexpected end of plist".into());
+    let token = tokens.get(*pos).ok_or_else(unexpected)?;
+    *pos += 1;
+
+    let name = match token {
+        XmlToken::Open(name) => name.as_str(),
+        XmlToken::Empty => return Ok(PlistValue::Other),
+        other => return Err(ImportError::Parse(format!("unexpected {:?}", other))),
+    };
+
+    match name {
+        "plist" => {
+            let value = parse_plist_value(tokens, pos)?;
+            *pos += 1; // </plist>
+            Ok(value)
+        }
+        "dict" => {
+            let mut entries = Vec::new();
+            loop {
+                match tokens.get(*pos).ok_or_else(unexpected)? {
+                    XmlToken::Close(_) => {
+                        *pos += 1;
+                        return Ok(PlistValue::Dict(entries));
+                    }
+                    XmlToken::Open(tag) if tag == "key" => {
+                        let key = match tokens.get(*pos + 1) {
+                            Some(XmlToken::Text(key)) => {
+                                *pos += 3;
+                                key.clone()
+                            }
+                            _ => {
+                                *pos += 2;
+                                String::new()
+                            }
+                        };
+                        let value = parse_plist_value(tokens, pos)?;
+                        entries.push((key, value));
+                    }
+                    other => return Err(ImportError::Parse(format!("unexpected {:?} in dict", other))),
+                }
+            }
+        }
+        "array" => {
+            while !matches!(tokens.get(*pos).ok_or_else(unexpected)?, XmlToken::Close(_)) {
+                parse_plist_value(tokens, pos)?;
+            }
+            *pos += 1;
+            Ok(PlistValue::Other)
+        }
+        _ => {
+            let text = match tokens.get(*pos) {
+                Some(XmlToken::Text(text)) => {
+                    *pos += 1;
+                    text.clone()
+                }
+                _ => String::new(),
+            };
+            *pos += 1; // closing tag
+            match name {
+                "real" | "integer" => text
+                    .trim()
+                    .parse()
+                    .map(PlistValue::Real)
+                    .map_err(|_| ImportError::Parse(format!("invalid number \"{}\"", text))),
+                _ => Ok(PlistValue::Other),
+            }
+        }
+    }
+}
+
+fn import_iterm(content: &str, fallback_name: &str) -> Result<Theme, ImportError> {
+    let tokens = tokenize_xml(content)?;
+    let root = parse_plist_value(&tokens, &mut 0)?;
+
+    let color = |key: &str| -> Result<String, ImportError> {
+        let dict = root.get(key).ok_or_else(|| ImportError::MissingColor(key.to_string()))?;
+        let component = |name: &str| dict.get(name).and_then(PlistValue::as_real).unwrap_or(0.0);
+        Ok(rgb_to_hex(
+            component("Red Component"),
+            component("Green Component"),
+            component("Blue Component"),
+        ))
+    };
+
+    let foreground = color("Foreground Color")?;
+    let cursor = color("Cursor Color").unwrap_or_else(|_| foreground.clone());
+    let palette = (0..16)
+        .map(|i| color(&format!("Ansi {} Color", i)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Theme {
+        name: fallback_name.to_string(),
+        background: color("Background Color")?,
+        foreground,
+        cursor,
+        palette,
+    })
+}
+
+// Alacritty
+
+fn flatten_toml(prefix: &str, value: &toml::Value, map: &mut HashMap<String, String>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten_toml(&path, value, map);
+            }
+        }
+        toml::Value::String(s) => {
+            map.insert(prefix.to_string(), s.clone());
+        }
+        _ => {}
+    }
+}
+
+/// Parse the block-mapping subset of YAML used by color scheme files into
+/// a map of dotted key paths (`colors.primary.background`) to scalar values.
+fn parse_yaml(content: &str) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    let mut stack: Vec<(usize, String)> = Vec::new();
+
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with("---") || trimmed.starts_with('-') {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+        let Some((key, value)) = trimmed.split_once(':') else {
+            continue;
+        };
+
+        while stack.last().is_some_and(|(i, _)| *i >= indent) {
+            stack.pop();
+        }
+
+        let key = unquote(key.trim()).to_string();
+        let value = strip_yaml_comment(value).trim();
+        if value.is_empty() {
+            stack.push((indent, key));
+            continue;
+        }
+
+        let mut path: Vec<&str> = stack.iter().map(|(_, k)| k.as_str()).collect();
+        path.push(&key);
+        map.insert(path.join("."), unquote(value).to_string());
+    }
+
+    map
+}
+
+fn strip_yaml_comment(value: &str) -> &str {
+    let value = value.trim();
+    if value.starts_with('"') || value.starts_with('\'') {
+        return value;
+    }
+    match value.find(" #") {
+        Some(idx) => &value[..idx],
+        None => value,
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    ['"', '\'']
+        .iter()
+        .find_map(|&quote| {
+            let inner = value.strip_prefix(quote)?;
+            inner.find(quote).map(|end| &inner[..end])
+        })
+        .unwrap_or(value)
+}
+
+fn import_alacritty(map: &HashMap<String, String>, fallback_name: &str) -> Result<Theme, ImportError> {
+    let color = |key: &str| -> Result<String, ImportError> {
+        let full_key = format!("colors.{}", key);
+        let value = map.get(&full_key).ok_or(ImportError::MissingColor(full_key))?;
+        normalize_color(value)
+    };
+
+    let foreground = color("primary.foreground")?;
+    let cursor = color("cursor.cursor").unwrap_or_else(|_| foreground.clone());
+
+    let mut palette = Vec::with_capacity(16);
+    for group in ["normal", "bright"] {
+        for name in ANSI_NAMES {
+            palette.push(color(&format!("{}.{}", group, name))?);
+        }
+    }
+
+    Ok(Theme {
+        name: fallback_name.to_string(),
+        background: color("primary.background")?,
+        foreground,
+        cursor,
+        palette,
+    })
+}
+
+// kitty
+
+fn import_kitty(content: &str, fallback_name: &str) -> Result<Theme, ImportError> {
+    let mut values = HashMap::new();
+    let mut name = fallback_name.to_string();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if let Some(comment) = line.strip_prefix("## name:") {
+            name = comment.trim().to_string();
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+            values.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    let color = |key: &str| -> Result<String, ImportError> {
+        let value = values.get(key).ok_or_else(|| ImportError::MissingColor(key.to_string()))?;
+        normalize_color(value)
+    };
+
+    let foreground = color("foreground")?;
+    let cursor = color("cursor").unwrap_or_else(|_| foreground.clone());
+    let palette = (0..16)
+        .map(|i| color(&format!("color{}", i)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Theme {
+        name,
+        background: color("background")?,
+        foreground,
+        cursor,
+        palette,
+    })
+}
+
+// Windows Terminal
+
+fn import_windows_terminal(content: &str, fallback_name: &str) -> Result<Theme, ImportError> {
+    let value: serde_json::Value = serde_json::from_str(content)
+        .map_err(|e| ImportError::Parse(e.to_string()))?;
+
+    // Accept a bare scheme object or a settings.json with a "schemes" list
+    let scheme = match value.get("schemes").and_then(|s| s.as_array()) {
+        Some(schemes) => schemes.first().ok_or_else(|| ImportError::Parse("no schemes found".into()))?,
+        None => &value,
+    };
+
+    let color = |key: &str| -> Result<String, ImportError> {
+        let value = scheme
+            .get(key)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ImportError::MissingColor(key.to_string()))?;
+        normalize_color(value)
+    };
+
+    let names = ["black", "red", "green", "yellow", "blue", "purple", "cyan", "white"];
+    let mut palette = Vec::with_capacity(16);
+    for name in names {
+        palette.push(color(name)?);
+    }
+    for name in names {
+        let mut bright = String::from("bright");
+        bright.push_str(&name[..1].to_uppercase());
+        bright.push_str(&name[1..]);
+        palette.push(color(&bright)?);
+    }
+
+    let foreground = color("foreground")?;
+    let cursor = color("cursorColor").unwrap_or_else(|_| foreground.clone());
+    let name = scheme
+        .get("name")
+        .and_then(|v| v.as_str())
+        .unwrap_or(fallback_name)
+        .to_string();
+
+    Ok(Theme {
+        name,
+        background: color("background")?,
+        foreground,
+        cursor,
+        palette,
+    })
+}
+
+// base16
+
+/// base16 slot used for each of the 16 terminal colors (base16-shell mapping).
+const BASE16_PALETTE: [&str; 16] = [
+    "base00", "base08", "base0B", "base0A", "base0D", "base0E", "base0C", "base05",
+    "base03", "base08", "base0B", "base0A", "base0D", "base0E", "base0C", "base07",
+];
+
+fn import_base16(map: &HashMap<String, String>, fallback_name: &str) -> Result<Theme, ImportError> {
+    // Older schemes keep the slots at the top level, newer ones under "palette"
+    let color = |key: &str| -> Result<String, ImportError> {
+        let value = map
+            .get(key)
+            .or_else(|| map.get(&format!("palette.{}", key)))
+            .or_else(|| map.get(&key.to_lowercase()))
+            .or_else(|| map.get(&format!("palette.{}", key.to_lowercase())))
+            .ok_or_else(|| ImportError::MissingColor(key.to_string()))?;
+        normalize_color(value)
+    };
+
+    let palette = BASE16_PALETTE
+        .iter()
+        .map(|key| color(key))
+        .collect::<Result<Vec<_>, _>>()?;
+    let name = map
+        .get("scheme")
+        .or_else(|| map.get("name"))
+        .cloned()
+        .unwrap_or_else(|| fallback_name.to_string());
+
+    Ok(Theme {
+        name,
+        background: color("base00")?,
+        foreground: color("base05")?,
+        cursor: color("base05")?,
+        palette,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/themes");
+
+    fn import_fixture(file: &str) -> Theme {
+        import_file(&Path::new(FIXTURES).join(file)).unwrap()
+    }
+
+    fn assert_fixture_colors(theme: &Theme) {
+        assert_eq!(theme.background, "#1d1f21");
+        assert_eq!(theme.foreground, "#c5c8c6");
+        assert_eq!(theme.palette.len(), 16);
+        assert_eq!(theme.palette[0], "#282a2e");
+        assert_eq!(theme.palette[1], "#a54242");
+        assert_eq!(theme.palette[15], "#ffffff");
+    }
+
+    #[test]
+    fn imports_iterm() {
+        let theme = import_fixture("tomorrow.itermcolors");
+        assert_eq!(theme.name, "tomorrow");
+        assert_eq!(theme.cursor, "#aeafad");
+        assert_fixture_colors(&theme);
+    }
+
+    #[test]
+    fn imports_alacritty_toml() {
+        let theme = import_fixture("tomorrow.toml");
+        assert_eq!(theme.cursor, "#aeafad");
+        assert_fixture_colors(&theme);
+    }
+
+    #[test]
+    fn imports_alacritty_yaml() {
+        let theme = import_fixture("tomorrow.yml");
+        assert_eq!(theme.cursor, "#aeafad");
+        assert_fixture_colors(&theme);
+    }
+
+    #[test]
+    fn imports_kitty() {
+        let theme = import_fixture("tomorrow.conf");
+        assert_eq!(theme.name, "Tomorrow Night");
+        assert_fixture_colors(&theme);
+    }
+
+    #[test]
+    fn imports_windows_terminal() {
+        let theme = import_fixture("tomorrow.json");
+        assert_eq!(theme.name, "Tomorrow Night");
+        assert_fixture_colors(&theme);
+    }
+
+    #[test]
+    fn imports_base16() {
+        let theme = import_fixture("base16-tomorrow-night.yaml");
+        assert_eq!(theme.name, "Tomorrow Night");
+        assert_eq!(theme.background, "#1d1f21");
+        assert_eq!(theme.foreground, "#c5c8c6");
+        assert_eq!(theme.palette[1], "#cc6666");
+        assert_eq!(theme.palette[8], "#969896");
+    }
+
+    #[test]
+    fn rejects_invalid_colors() {
+        let result = import_str("foreground nothex\n", Format::Kitty, "broken");
+        assert!(matches!(result, Err(ImportError::InvalidColor(_))));
+    }
+
+    #[test]
+    fn rejects_unknown_extension() {
+        assert_eq!(detect_format(Path::new("scheme.txt"), ""), None);
+    }
+
+    #[test]
+    fn saving_keeps_existing_theme_files() {
+        let dir = std::env::temp_dir().join(format!("rustyterm-test-themes-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut theme = import_fixture("tomorrow.itermcolors");
+        theme.name = "My Theme".to_string();
+
+        let first = save_theme_in(&dir, &mut theme.clone()).unwrap();
+        assert_eq!(first, dir.join("My_Theme.toml"));
+        let edited = fs::read_to_string(&first).unwrap();
+
+        let second = save_theme_in(&dir, &mut theme).unwrap();
+        assert_eq!(second, dir.join("My_Theme-2.toml"));
+        assert_eq!(theme.name, "My Theme 2");
+        assert_eq!(fs::read_to_string(&first).unwrap(), edited);
+        assert!(fs::read_to_string(&second).unwrap().contains("\"My Theme 2\""));
+
+        theme.name = "My Theme".to_string();
+        assert_eq!(save_theme_in(&dir, &mut theme).unwrap(), dir.join("My_Theme-3.toml"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
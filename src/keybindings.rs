@@ -0,0 +1,371 @@
+//! Keyboard shortcuts.
+//!
+//! Every bindable action has a name, the GAction it activates and its
+//! default shortcuts. The `[keybindings]` table of the config maps
+//! accelerators to action names, replacing the default binding of that
+//! accelerator, or removes it with `"unset"`.
+
+use gtk4::prelude::*;
+use gtk4::{gio, Application};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Value that removes the binding of an accelerator.
+pub const UNSET: &str = "unset";
+
+/// Actions without a parameter: name, detailed GAction name and default
+/// accelerators.
+const ACTIONS: &[(&str, &str, &[&str])] = &[
+    ("new-window", "win.new-window", &["<Ctrl><Shift>n"]),
+    ("new-tab", "win.new-tab", &["<Ctrl><Shift>t"]),
+    ("close-tab", "win.close-tab", &["<Ctrl><Shift>w"]),
+    ("next-tab", "win.next-tab", &["<Ctrl>Page_Down"]),
+    ("previous-tab", "win.previous-tab", &["<Ctrl>Page_Up"]),
+    ("last-used-tab", "win.last-used-tab", &["<Ctrl>Tab"]),
+    ("move-tab-left", "win.move-tab-left", &["<Ctrl><Shift>Page_Up"]),
+    ("move-tab-right", "win.move-tab-right", &["<Ctrl><Shift>Page_Down"]),
+    ("copy", "win.copy", &["<Ctrl><Shift>c"]),
+    ("paste", "win.paste", &["<Ctrl><Shift>v"]),
+    ("search", "win.search", &["<Ctrl><Shift>f"]),
+    ("search-all-tabs", "win.search-all-tabs", &["<Ctrl><Shift><Alt>f"]),
+    ("previous-prompt", "win.previous-prompt", &["<Ctrl><Shift>Up"]),
+    ("next-prompt", "win.next-prompt", &["<Ctrl><Shift>Down"]),
+    ("copy-last-output", "win.copy-last-output", &["<Ctrl><Shift><Alt>c"]),
+    ("split-right", "win.split-right", &["<Ctrl><Shift>e"]),
+    ("split-down", "win.split-down", &["<Ctrl><Shift>o"]),
+    ("close-pane", "win.close-pane", &["<Ctrl><Shift>x"]),
+    ("focus-pane-left", "win.focus-pane::left", &["<Alt>Left"]),
+    ("focus-pane-right", "win.focus-pane::right", &["<Alt>Right"]),
+    ("focus-pane-up", "win.focus-pane::up", &["<Alt>Up"]),
+    ("focus-pane-down", "win.focus-pane::down", &["<Alt>Down"]),
+    ("resize-pane-left", "win.resize-pane::left", &["<Alt><Shift>Left"]),
+    ("resize-pane-right", "win.resize-pane::right", &["<Alt><Shift>Right"]),
+    ("resize-pane-up", "win.resize-pane::up", &["<Alt><Shift>Up"]),
+    ("resize-pane-down", "win.resize-pane::down", &["<Alt><Shift>Down"]),
+    ("zoom-in", "win.zoom-in", &["<Ctrl>plus", "<Ctrl>equal"]),
+    ("zoom-out", "win.zoom-out", &["<Ctrl>minus"]),
+    ("zoom-reset", "win.zoom-reset", &["<Ctrl>0"]),
+    ("import-theme", "win.import-theme", &[]),
+    ("export-theme", "win.export-theme", &[]),
+    ("quit", "app.quit", &["<Ctrl><Shift>q"]),
+];
+
+/// Default accelerators of actions that take an argument, written as in
+/// the `[keybindings]` table.
+const ARGUMENT_ACTIONS: &[(&str, &str)] = &[
+    ("<Alt>1", "goto-tab:1"),
+    ("<Alt>2", "goto-tab:2"),
+    ("<Alt>3", "goto-tab:3"),
+    ("<Alt>4", "goto-tab:4"),
+    ("<Alt>5", "goto-tab:5"),
+    ("<Alt>6", "goto-tab:6"),
+    ("<Alt>7", "goto-tab:7"),
+    ("<Alt>8", "goto-tab:8"),
+    ("<Alt>9", "goto-tab:9"),
+];
+
+/// Invalid entry in the `[keybindings]` table.
+#[derive(Debug)]
+pub struct KeybindingError(String);
+
+impl fmt::Display for KeybindingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for KeybindingError {}
+
+/// Accelerators of every action, after applying the config.
+#[derive(Debug, Default)]
+pub struct Keybindings {
+    /// Normalized accelerator to detailed action name
+    bindings: BTreeMap<String, String>,
+    /// Entries that took the only shortcut of another action, described
+    pub shadowed: Vec<String>,
+}
+
+impl Keybindings {
+    /// Combine the defaults with the `[keybindings]` table. Invalid entries
+    /// are skipped and reported; the other bindings still apply.
+    /// GTK must be initialized to parse accelerators.
+    pub fn new(table: &BTreeMap<String, String>) -> (Self, Vec<KeybindingError>) {
+        Self::with_normalizer(table, normalize)
+    }
+
+    /// [`Keybindings::new`] with accelerators spelled by `normalize`, so
+    /// the merging can be tested without GTK.
+    fn with_normalizer(
+        table: &BTreeMap<String, String>,
+        normalize: impl Fn(&str) -> Option<String>,
+    ) -> (Self, Vec<KeybindingError>) {
+        let mut bindings = BTreeMap::new();
+        // Names of the actions with a default shortcut, by detailed name
+        let mut names = BTreeMap::new();
+        for (name, action, accels) in ACTIONS {
+            for accel in *accels {
+                if let Some(accel) = normalize(accel) {
+                    bindings.insert(accel, action.to_string());
+                    names.insert(action.to_string(), *name);
+                }
+            }
+        }
+        for (accel, value) in ARGUMENT_ACTIONS {
+            if let (Some(accel), Ok(action)) = (normalize(accel), parse_action(value)) {
+                bindings.insert(accel, action.clone());
+                names.insert(action, *value);
+            }
+        }
+
+        let mut errors = Vec::new();
+        // The value each normalized accelerator got from the table, to
+        // catch two spellings of the same shortcut
+        let mut configured: BTreeMap<String, &str> = BTreeMap::new();
+        // Default bindings replaced by an entry for another action
+        let mut replaced = Vec::new();
+        for (accel, value) in table {
+            let Some(normalized) = normalize(accel) else {
+                errors.push(KeybindingError(format!("Invalid shortcut \"{}\"", accel)));
+                continue;
+            };
+            if let Some(previous) = configured.get(&normalized) {
+                if *previous != value.as_str() {
+                    errors.push(KeybindingError(format!(
+                        "Shortcut \"{}\" is bound to both \"{}\" and \"{}\"",
+                        accel, previous, value
+                    )));
+                }
+                continue;
+            }
+            configured.insert(normalized.clone(), value);
+
+            if value == UNSET {
+                bindings.remove(&normalized);
+                continue;
+            }
+            match parse_action(value) {
+                Ok(action) => {
+                    if let Some(previous) = bindings.insert(normalized, action.clone())
+                        && previous != action
+                    {
+                        replaced.push((accel, value, previous));
+                    }
+                }
+                Err(e) => errors.push(KeybindingError(format!("Shortcut \"{}\": {}", accel, e))),
+            }
+        }
+
+        // Moving an action to another shortcut is fine, losing it is not
+        let shadowed = replaced
+            .into_iter()
+            .filter(|(_, _, previous)| !bindings.values().any(|action| action == previous))
+            .map(|(accel, value, previous)| {
+                let name = names.get(&previous).copied().unwrap_or(previous.as_str());
+                format!("\"{}\" = \"{}\" leaves \"{}\" without a shortcut", accel, value, name)
+            })
+            .collect();
+
+        (Self { bindings, shadowed }, errors)
+    }
+
+    /// Replace every accelerator of the application with these bindings.
+    pub fn apply(&self, app: &Application) {
+        for action in app.list_action_descriptions() {
+            app.set_accels_for_action(&action, &[]);
+        }
+
+        let mut by_action: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (accel, action) in &self.bindings {
+            by_action.entry(action).or_default().push(accel);
+        }
+        for (action, accels) in by_action {
+            app.set_accels_for_action(action, &accels);
+        }
+    }
+}
+
+/// Canonical spelling of an accelerator, so `<Control>T` and `<Ctrl>t`
+/// are recognized as the same shortcut.
+fn normalize(accel: &str) -> Option<String> {
+    let (key, modifiers) = gtk4::accelerator_parse(accel)?;
+    Some(gtk4::accelerator_name(key.to_lower(), modifiers).to_string())
+}
+
+/// Turn an action name, or `name:argument` for actions that take one,
+/// into a detailed GAction name.
+fn parse_action(value: &str) -> Result<String, String> {
+    let (name, argument) = match value.split_once(':') {
+        Some((name, argument)) => (name, Some(argument)),
+        None => (value, None),
+    };
+
+    let (action, argument) = match (name, argument) {
+        ("goto-tab", Some(number)) => {
+            let number = number.parse::<i32>().ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| format!("invalid tab number \"{}\"", number))?;
+            ("win.goto-tab", number.to_variant())
+        }
+        ("set-theme", Some(theme)) => ("win.set-theme", theme.to_variant()),
+        ("send-text", Some(text)) => ("win.send-bytes", decode_escapes(text)?.to_variant()),
+        ("goto-tab" | "set-theme" | "send-text", None) => {
+            return Err(format!("\"{}\" needs an argument, as in \"{}:…\"", name, name));
+        }
+        (_, None) => {
+            return ACTIONS.iter()
+                .find(|(action, _, _)| *action == name)
+                .map(|(_, detailed, _)| detailed.to_string())
+                .ok_or_else(|| format!("unknown action \"{}\"", name));
+        }
+        (_, Some(_)) => return Err(format!("\"{}\" does not take an argument", name)),
+    };
+
+    Ok(gio::Action::print_detailed_name(action, Some(&argument)).to_string())
+}
+
+/// Bytes to send for a `send-text` binding: the text itself, where `\xNN`
+/// stands for the byte with hexadecimal value NN.
+fn decode_escapes(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("\\x") {
+        bytes.extend_from_slice(&rest.as_bytes()[..start]);
+        let hex = rest.get(start + 2..start + 4)
+            .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| format!("invalid escape in \"{}\", expected \\xNN", text))?;
+        bytes.push(u8::from_str_radix(hex, 16).map_err(|e| e.to_string())?);
+        rest = &rest[start + 4..];
+    }
+    bytes.extend_from_slice(rest.as_bytes());
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_escapes_become_bytes() {
+        assert_eq!(decode_escapes("\\x1b[A").unwrap(), b"\x1b[A");
+        assert_eq!(decode_escapes("ls\\x0d").unwrap(), b"ls\r");
+        assert_eq!(decode_escapes("caf\u{e9}\\xff").unwrap(), "caf\u{e9}".bytes().chain([0xff]).collect::<Vec<_>>());
+        assert!(decode_escapes("\\x1").is_err());
+        assert!(decode_escapes("\\xzz").is_err());
+    }
+
+    #[test]
+    fn actions_resolve_to_detailed_names() {
+        assert_eq!(parse_action("new-tab").unwrap(), "win.new-tab");
+        assert_eq!(parse_action("focus-pane-left").unwrap(), "win.focus-pane::left");
+        assert_eq!(parse_action("goto-tab:3").unwrap(), "win.goto-tab(3)");
+        assert_eq!(parse_action("set-theme:dracula").unwrap(), "win.set-theme::dracula");
+        assert!(parse_action("goto-tab:0").is_err());
+        assert!(parse_action("goto-tab").is_err());
+        assert!(parse_action("set-theme").is_err());
+        assert!(parse_action("new-tab:1").is_err());
+        assert!(parse_action("nope").is_err());
+    }
+
+    /// Canonical spelling of the accelerators used in these tests, like
+    /// GTK's: modifiers in a fixed order whatever their spelling, and keys
+    /// in lowercase.
+    fn test_normalize(accel: &str) -> Option<String> {
+        let mut rest = accel;
+        let mut modifiers = std::collections::BTreeSet::new();
+        while let Some(after) = rest.strip_prefix('<') {
+            let (modifier, after) = after.split_once('>')?;
+            modifiers.insert(match modifier {
+                "Ctrl" | "Control" => "<Control>",
+                "Shift" => "<Shift>",
+                "Alt" => "<Alt>",
+                _ => return None,
+            });
+            rest = after;
+        }
+        let valid_key = !rest.is_empty() && rest.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        valid_key.then(|| format!("{}{}", modifiers.into_iter().collect::<String>(), rest.to_lowercase()))
+    }
+
+    fn resolve(entries: &[(&str, &str)]) -> (Keybindings, Vec<KeybindingError>) {
+        let table = entries.iter().map(|(accel, value)| (accel.to_string(), value.to_string())).collect();
+        Keybindings::with_normalizer(&table, test_normalize)
+    }
+
+    /// Accelerators bound to an action, in the spelling of `accels`.
+    fn accels_of<'a>(keybindings: &Keybindings, action: &str, accels: &[&'a str]) -> Vec<&'a str> {
+        accels.iter()
+            .copied()
+            .filter(|accel| {
+                keybindings.bindings.get(&test_normalize(accel).unwrap()).is_some_and(|bound| bound == action)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn defaults_apply_without_entries() {
+        let (defaults, errors) = resolve(&[]);
+        assert!(errors.is_empty());
+        assert!(defaults.shadowed.is_empty());
+        assert_eq!(accels_of(&defaults, "win.copy", &["<Ctrl><Shift>c", "<Ctrl>c"]), ["<Ctrl><Shift>c"]);
+        assert_eq!(accels_of(&defaults, "win.goto-tab(1)", &["<Alt>1"]), ["<Alt>1"]);
+    }
+
+    #[test]
+    fn entries_unset_override_and_add_bindings() {
+        let (keybindings, errors) = resolve(&[
+            ("<Ctrl><Shift>t", "unset"),
+            ("<Ctrl>t", "new-tab"),
+            ("<Ctrl><Shift>n", "copy"),
+        ]);
+        assert!(errors.is_empty());
+        assert_eq!(accels_of(&keybindings, "win.new-tab", &["<Ctrl><Shift>t", "<Ctrl>t"]), ["<Ctrl>t"]);
+        assert!(!keybindings.bindings.contains_key(&test_normalize("<Ctrl><Shift>t").unwrap()));
+        assert_eq!(
+            accels_of(&keybindings, "win.copy", &["<Ctrl><Shift>c", "<Ctrl><Shift>n"]),
+            ["<Ctrl><Shift>c", "<Ctrl><Shift>n"]
+        );
+        assert_eq!(keybindings.shadowed, ["\"<Ctrl><Shift>n\" = \"copy\" leaves \"new-window\" without a shortcut"]);
+    }
+
+    #[test]
+    fn invalid_and_conflicting_entries_are_reported() {
+        let (keybindings, errors) = resolve(&[
+            ("<Control>q", "close-tab"),
+            ("<Ctrl>Q", "new-tab"),
+            ("<Ctrl><Shift>c", "new-tab"),
+            ("<Ctrl><Shift>v", "goto-tab:0"),
+            ("<Hyper>x", "copy"),
+        ]);
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors.iter().any(|e| e.to_string().contains("bound to both")), "{:?}", errors);
+        assert!(errors.iter().any(|e| e.to_string() == "Invalid shortcut \"<Hyper>x\""), "{:?}", errors);
+        assert_eq!(
+            accels_of(&keybindings, "win.close-tab", &["<Ctrl><Shift>w", "<Ctrl>q"]),
+            ["<Ctrl><Shift>w", "<Ctrl>q"]
+        );
+        assert_eq!(accels_of(&keybindings, "win.paste", &["<Ctrl><Shift>v"]), ["<Ctrl><Shift>v"]);
+        assert!(accels_of(&keybindings, "win.copy", &["<Ctrl><Shift>c"]).is_empty());
+        assert_eq!(keybindings.shadowed, ["\"<Ctrl><Shift>c\" = \"new-tab\" leaves \"copy\" without a shortcut"]);
+    }
+
+    #[test]
+    #[ignore = "initializes GTK, which needs a display"]
+    fn gtk_spellings_are_normalized() {
+        gtk4::init().unwrap();
+        assert_eq!(normalize("<Control>T"), normalize("<Ctrl>t"));
+        assert_eq!(normalize("<Ctrl><Shift>c"), normalize("<Shift><Control>C"));
+        assert_eq!(normalize("<Ctrl>nope"), None);
+        for (_, _, accels) in ACTIONS {
+            for accel in *accels {
+                assert!(normalize(accel).is_some(), "{}", accel);
+            }
+        }
+    }
+
+    #[test]
+    fn default_arguments_are_valid() {
+        for (_, value) in ARGUMENT_ACTIONS {
+            assert!(parse_action(value).is_ok(), "{}", value);
+        }
+    }
+}
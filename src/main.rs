@@ -1,14 +1,116 @@
 mod app;
+mod banner;
+mod cli;
 mod config;
+mod export;
+mod global_search;
+mod hyperlink;
+mod import;
+mod keybindings;
+mod matchers;
+mod pane;
+mod remote;
 mod resize;
+mod search;
+mod session;
+mod shell_integration;
 mod tab;
 mod terminal;
 mod theme;
 mod window;
 
 use app::RustyTermApp;
+use cli::{Options, Tool, USAGE};
+use config::Config;
+use export::ExportFormat;
+use std::path::Path;
 
 fn main() -> glib::ExitCode {
-    let app = RustyTermApp::new();
+    let args: Vec<String> = std::env::args().collect();
+
+    // Validate locally so errors and help reach the terminal that ran us;
+    // the application parses the arguments again wherever it runs
+    let options = match Options::parse(args.get(1..).unwrap_or_default()) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return glib::ExitCode::FAILURE;
+        }
+    };
+    if options.help {
+        print!("{}", USAGE);
+        return glib::ExitCode::SUCCESS;
+    }
+    if let Some(path) = &options.config {
+        Config::set_config_file(std::path::absolute(path).unwrap_or_else(|_| path.clone()));
+    }
+    match &options.tool {
+        Some(Tool::ImportTheme(path)) => return import_theme(path),
+        Some(Tool::ExportTheme { format, theme }) => return export_theme(format, theme.as_deref()),
+        Some(Tool::PrintDefaultConfig) => {
+            print!("{}", Config::default_template());
+            return glib::ExitCode::SUCCESS;
+        }
+        None => {}
+    }
+    if let Some(class) = &options.class {
+        glib::set_prgname(Some(class.as_str()));
+    }
+
+    let app = RustyTermApp::new(&options);
     app.run()
 }
+
+/// `rustyterm --import-theme <file>`: convert a color scheme from another
+/// terminal and save it to the user themes directory.
+fn import_theme(path: &Path) -> glib::ExitCode {
+    let mut theme = match import::import_file(path) {
+        Ok(theme) => theme,
+        Err(e) => {
+            eprintln!("Failed to import {}: {}", path.display(), e);
+            return glib::ExitCode::FAILURE;
+        }
+    };
+
+    match import::save_theme(&mut theme) {
+        Ok(saved) => {
+            println!("Imported theme \"{}\" to {}", theme.name, saved.display());
+            glib::ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Failed to save theme \"{}\": {}", theme.name, e);
+            glib::ExitCode::FAILURE
+        }
+    }
+}
+
+/// `rustyterm --export-theme <format> [theme]`: print a theme (the configured
+/// one by default) in another terminal's format.
+fn export_theme(format: &str, theme_name: Option<&str>) -> glib::ExitCode {
+    let Some(format) = ExportFormat::from_name(format) else {
+        eprintln!(
+            "Usage: rustyterm --export-theme <{}> [theme]",
+            ExportFormat::NAMES.join("|")
+        );
+        return glib::ExitCode::FAILURE;
+    };
+
+    let theme_name = theme_name
+        .map(String::from)
+        .unwrap_or_else(|| Config::load().unwrap_or_default().theme);
+    let Some(theme) = theme::get_theme_by_name(&theme_name) else {
+        eprintln!("Unknown theme \"{}\"", theme_name);
+        return glib::ExitCode::FAILURE;
+    };
+
+    match export::export_theme(&theme, format) {
+        Ok(output) => {
+            print!("{}", output);
+            glib::ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Failed to export theme \"{}\": {}", theme.name, e);
+            glib::ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Clickable text in terminal output.
+//!
+//! URLs, email addresses and `path:line:col` locations printed by
+//! compilers are recognized out of the box, and `[[matchers]]` in the
+//! config adds more. VTE underlines a match under the pointer; Ctrl+click
+//! runs the action of its matcher.
+
+use gtk4::prelude::*;
+use gtk4::gio;
+use vte4::Terminal;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::config::{MatchAction, Matcher};
+use crate::hyperlink::open_uri;
+use crate::search::{PCRE2_MULTILINE, PCRE2_UCP, PCRE2_UTF};
+use crate::terminal::expand_home;
+
+const URL_PATTERN: &str =
+    r#"(?i)\b(?:(?:https?|ftp|file)://|www\.)[^\s<>"'`(){}\[\]]*[^\s<>"'`(){}\[\].,;:!?]"#;
+
+const EMAIL_PATTERN: &str = r"(?i)\b(?:mailto:)?[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b";
+
+/// A file name with an extension followed by a line and optional column,
+/// as in `src/main.rs:12:5`. Without a directory the extension must be one
+/// of source code, so `example.com:443` is not taken for a location.
+const LOCATION_PATTERN: &str = concat!(
+    r"(?<![\w./~+-])",
+    r"(?:(?:(?:~|\.{1,2})?/|[\w.+-]+/)(?:[\w.+-]+/)*[\w.+-]*\.[A-Za-z]\w*",
+    r"|[\w+-][\w.+-]*\.(?:c|cc|cpp|cs|css|cxx|dart|erl|ex|exs|go|h|hh|hpp|hs|html|java|js|json|jsx",
+    r"|kt|lua|md|ml|nim|php|py|rb|rs|scala|scss|sh|sql|swift|toml|ts|tsx|txt|vue|xml|yaml|yml|zig))",
+    r":\d+(?::\d+)?",
+);
+
+/// The matchers that apply without any configuration.
+pub fn builtin() -> Vec<Matcher> {
+    [
+        (URL_PATTERN, MatchAction::Open),
+        (EMAIL_PATTERN, MatchAction::Open),
+        (LOCATION_PATTERN, MatchAction::Edit),
+    ]
+    .into_iter()
+    .map(|(regex, action)| Matcher {
+        regex: regex.to_string(),
+        action,
+        command: None,
+    })
+    .collect()
+}
+
+pub fn compile(pattern: &str) -> Result<vte4::Regex, glib::Error> {
+    vte4::Regex::for_match(pattern, PCRE2_UTF | PCRE2_UCP | PCRE2_MULTILINE)
+}
+
+/// Problems with configured matchers, which are skipped.
+pub fn check(matchers: &[Matcher]) -> Vec<String> {
+    let mut errors = Vec::new();
+    for matcher in matchers {
+        if let Err(e) = compile(&matcher.regex) {
+            errors.push(format!("\"{}\": {}", matcher.regex, e.message()));
+        } else if matcher.action == MatchAction::Command && matcher.command.is_none() {
+            errors.push(format!("\"{}\": the command action needs a command", matcher.regex));
+        }
+    }
+    errors
+}
+
+/// Run the action of `matcher` on the `text` it matched in `terminal`.
+/// Relative paths are taken from `directory`, the terminal's directory.
+pub fn activate(
+    terminal: &Terminal,
+    text: &str,
+    matcher: &Matcher,
+    directory: Option<&Path>,
+) -> Result<(), String> {
+    match matcher.action {
+        MatchAction::Open => open_uri(terminal, &uri_for(text, directory)),
+        MatchAction::Edit => {
+            let (path, line, column) = parse_location(text);
+            let path = resolve(path, directory);
+            if !path.is_file() {
+                return Err(format!("No such file: {}", path.display()));
+            }
+            let editor = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).ok();
+            match editor.filter(|editor| !editor.trim().is_empty()) {
+                // Terminal editors need a terminal, so the editor gets a tab
+                Some(editor) => terminal
+                    .activate_action("win.new-tab-command", Some(&editor_command(&editor, &path, line, column).to_variant()))
+                    .map_err(|e| e.to_string()),
+                None => open_uri(terminal, &gio::File::for_path(&path).uri()),
+            }
+        }
+        MatchAction::Copy => {
+            terminal.clipboard().set_text(text);
+            Ok(())
+        }
+        MatchAction::Command => {
+            let argv = expand_command(matcher.command.as_deref().unwrap_or_default(), text);
+            if argv.is_empty() {
+                return Err(format!("No command to run for \"{}\"", text));
+            }
+            let launcher = gio::SubprocessLauncher::new(gio::SubprocessFlags::NONE);
+            if let Some(directory) = directory {
+                launcher.set_cwd(directory);
+            }
+            let argv: Vec<&OsStr> = argv.iter().map(OsStr::new).collect();
+            launcher.spawn(&argv)
+                .map(|_| ())
+                .map_err(|e| format!("Failed to run {}: {}", argv[0].to_string_lossy(), e.message()))
+        }
+    }
+}
+
+/// URI for matched text: URLs as they are, email addresses as `mailto:`
+/// and anything else as a file.
+fn uri_for(text: &str, directory: Option<&Path>) -> String {
+    let lower = text.to_ascii_lowercase();
+    if text.contains("://") || lower.starts_with("mailto:") {
+        text.to_string()
+    } else if lower.starts_with("www.") {
+        format!("http://{}", text)
+    } else if text.contains('@') && !text.contains('/') {
+        format!("mailto:{}", text)
+    } else {
+        let (path, _, _) = parse_location(text);
+        gio::File::for_path(resolve(path, directory)).uri().to_string()
+    }
+}
+
+/// Split `path:line:col` into its parts; line and column are optional.
+fn parse_location(text: &str) -> (&str, Option<u32>, Option<u32>) {
+    let Some((rest, last)) = text.rsplit_once(':') else {
+        return (text, None, None);
+    };
+    let Ok(last) = last.parse::<u32>() else {
+        return (text, None, None);
+    };
+    match rest.rsplit_once(':').map(|(path, line)| (path, line.parse::<u32>())) {
+        Some((path, Ok(line))) => (path, Some(line), Some(last)),
+        _ => (rest, Some(last), None),
+    }
+}
+
+fn resolve(path: &str, directory: Option<&Path>) -> PathBuf {
+    let path = expand_home(path);
+    match directory {
+        Some(directory) if path.is_relative() => directory.join(path),
+        _ => path,
+    }
+}
+
+/// `$EDITOR` with its arguments, opening `path` at `line` with the `+LINE`
+/// option most terminal editors understand. Editors known to take a column
+/// too are given the `column` in their own syntax.
+fn editor_command(editor: &str, path: &Path, line: Option<u32>, column: Option<u32>) -> Vec<String> {
+    let mut argv: Vec<String> = editor.split_whitespace().map(String::from).collect();
+    let path = path.to_string_lossy().into_owned();
+    let program = argv.first()
+        .and_then(|program| Path::new(program).file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match (line, column) {
+        (Some(line), Some(column)) => match program.as_str() {
+            "vi" | "vim" | "nvim" | "gvim" => argv.push(format!("+call cursor({}, {})", line, column)),
+            "emacs" | "emacsclient" | "micro" => argv.push(format!("+{}:{}", line, column)),
+            "nano" => argv.push(format!("+{},{}", line, column)),
+            // Helix takes the position as part of the file name
+            "hx" | "helix" => {
+                argv.push(format!("{}:{}:{}", path, line, column));
+                return argv;
+            }
+            _ => argv.push(format!("+{}", line)),
+        },
+        (Some(line), None) => argv.push(format!("+{}", line)),
+        _ => {}
+    }
+    argv.push(path);
+    argv
+}
+
+/// Words of a `command` template, with `{}` replaced by the matched text.
+/// The text stays a single argument, so it is never seen by a shell.
+fn expand_command(template: &str, text: &str) -> Vec<String> {
+    template.split_whitespace().map(|word| word.replace("{}", text)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locations_split_into_path_line_and_column() {
+        assert_eq!(parse_location("src/main.rs:12:5"), ("src/main.rs", Some(12), Some(5)));
+        assert_eq!(parse_location("main.c:7"), ("main.c", Some(7), None));
+        assert_eq!(parse_location("README.md"), ("README.md", None, None));
+        assert_eq!(parse_location("a:b.rs:3"), ("a:b.rs", Some(3), None));
+    }
+
+    fn is_location(text: &str) -> bool {
+        let regex = compile(LOCATION_PATTERN).unwrap();
+        regex.substitute(text, "<$0>", 0).is_ok_and(|marked| marked == format!("<{}>", text))
+    }
+
+    #[test]
+    fn locations_need_a_directory_or_a_source_extension() {
+        assert!(is_location("src/main.rs:12:5"));
+        assert!(is_location("main.c:7"));
+        assert!(is_location("./build/out.o:3"));
+        assert!(is_location("/usr/include/stdio.h:12"));
+        assert!(is_location("~/notes.txt:1"));
+        assert!(!is_location("example.com:443"));
+        assert!(!is_location("db.internal:5432"));
+        assert!(!is_location("10.0.0.1:22"));
+    }
+
+    #[test]
+    fn editor_opens_at_line() {
+        assert_eq!(
+            editor_command("emacs -nw", Path::new("/tmp/a.rs"), Some(3), None),
+            ["emacs", "-nw", "+3", "/tmp/a.rs"]
+        );
+        assert_eq!(editor_command("vim", Path::new("a.rs"), None, None), ["vim", "a.rs"]);
+    }
+
+    #[test]
+    fn editor_opens_at_column_when_it_can() {
+        let path = Path::new("a.rs");
+        assert_eq!(editor_command("/usr/bin/nvim", path, Some(3), Some(5)), ["/usr/bin/nvim", "+call cursor(3, 5)", "a.rs"]);
+        assert_eq!(editor_command("emacsclient -t", path, Some(3), Some(5)), ["emacsclient", "-t", "+3:5", "a.rs"]);
+        assert_eq!(editor_command("nano", path, Some(3), Some(5)), ["nano", "+3,5", "a.rs"]);
+        assert_eq!(editor_command("hx", path, Some(3), Some(5)), ["hx", "a.rs:3:5"]);
+        assert_eq!(editor_command("ed", path, Some(3), Some(5)), ["ed", "+3", "a.rs"]);
+    }
+
+    #[test]
+    fn command_template_keeps_text_as_one_argument() {
+        assert_eq!(
+            expand_command("xdg-open https://jira.example.com/browse/{}", "JIRA-1"),
+            ["xdg-open", "https://jira.example.com/browse/JIRA-1"]
+        );
+        assert_eq!(expand_command("echo {}", "a; rm -rf ~"), ["echo", "a; rm -rf ~"]);
+    }
+
+    #[test]
+    fn urls_and_addresses_keep_their_scheme() {
+        assert_eq!(uri_for("https://example.com/a", None), "https://example.com/a");
+        assert_eq!(uri_for("www.example.com", None), "http://www.example.com");
+        assert_eq!(uri_for("user@example.com", None), "mailto:user@example.com");
+        assert_eq!(uri_for("mailto:user@example.com", None), "mailto:user@example.com");
+    }
+}
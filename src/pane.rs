@@ -0,0 +1,398 @@
+use gtk4::prelude::*;
+use gtk4::{Align, Box, Button, DrawingArea, GestureClick, Label, Orientation, Overlay, PopoverMenu, PropagationPhase};
+use gtk4::gio::Menu;
+use gtk4::gdk::{ModifierType, Rectangle};
+use vte4::TerminalExt;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::banner::Banner;
+use crate::config::{Config, ExitAction, Profile};
+use crate::hyperlink;
+use crate::matchers;
+use crate::shell_integration::Command;
+use crate::terminal::{SpawnOptions, TerminalWidget};
+
+/// Direction used for moving focus between panes and resizing splits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "left" => Some(Direction::Left),
+            "right" => Some(Direction::Right),
+            "up" => Some(Direction::Up),
+            "down" => Some(Direction::Down),
+            _ => None,
+        }
+    }
+
+    /// Orientation of the `Paned` whose divider moves along this direction.
+    pub fn orientation(self) -> Orientation {
+        match self {
+            Direction::Left | Direction::Right => Orientation::Horizontal,
+            Direction::Up | Direction::Down => Orientation::Vertical,
+        }
+    }
+}
+
+/// Width of the strip next to the terminal marking each command's exit
+/// status.
+const GUTTER_WIDTH: i32 = 6;
+
+/// Pixels around a mark of the strip that hovering and clicks reach.
+const GUTTER_REACH: f64 = 4.0;
+
+/// Source of pane ids used by remote control.
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+/// A single terminal inside a tab's split tree.
+pub struct Pane {
+    pub id: u32,
+    pub container: Overlay,
+    pub terminal: TerminalWidget,
+    banner: Banner,
+    restart_button: Button,
+    /// Target of the hyperlink under the pointer
+    link_preview: Label,
+    /// Exit statuses of the commands in the scrollback
+    gutter: DrawingArea,
+    on_exit: ExitAction,
+    exit_callback: RefCell<Option<Rc<dyn Fn()>>>,
+    closed: Cell<bool>,
+    popover: RefCell<Option<PopoverMenu>>,
+}
+
+impl Pane {
+    /// Create a pane and start the command described by `options` in it.
+    pub fn new(profile: &Profile, options: &SpawnOptions, config: &Config) -> Rc<Self> {
+        let terminal = TerminalWidget::new(profile, options);
+        terminal.set_matchers(&config.matchers);
+        terminal.set_shell_integration(config.shell_integration);
+        terminal.widget().set_hexpand(true);
+        terminal.widget().set_vexpand(true);
+
+        // Shown once the shell integration reports a command
+        let gutter = DrawingArea::new();
+        gutter.set_content_width(GUTTER_WIDTH);
+        gutter.set_has_tooltip(true);
+        gutter.set_visible(false);
+
+        let body = Box::new(Orientation::Horizontal, 0);
+        body.append(terminal.widget());
+        body.append(&gutter);
+
+        // Overlay so that bars and banners can be stacked on top of the terminal
+        let container = Overlay::new();
+        container.set_child(Some(&body));
+        container.set_hexpand(true);
+        container.set_vexpand(true);
+
+        // Reports how the process ended
+        let banner = Banner::new();
+        banner.revealer.set_valign(Align::Start);
+        container.add_overlay(&banner.revealer);
+
+        let restart_button = Button::with_label("Restart");
+        restart_button.set_valign(Align::Center);
+        banner.append_button(&restart_button);
+
+        let link_preview = Label::new(None);
+        link_preview.add_css_class("hyperlink-preview");
+        link_preview.add_css_class("osd");
+        link_preview.set_halign(Align::Start);
+        link_preview.set_valign(Align::End);
+        link_preview.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+        link_preview.set_can_target(false);
+        link_preview.set_visible(false);
+        container.add_overlay(&link_preview);
+
+        let pane = Rc::new(Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            container,
+            terminal,
+            banner,
+            restart_button,
+            link_preview,
+            gutter,
+            on_exit: profile.on_exit,
+            exit_callback: RefCell::new(None),
+            closed: Cell::new(false),
+            popover: RefCell::new(None),
+        });
+        pane.setup_context_menu();
+        pane.setup_matches();
+        pane.setup_link_preview();
+        pane.setup_gutter();
+        pane.setup_child_exit();
+        pane.spawn();
+
+        pane
+    }
+
+    /// Start (or restart) the pane's command.
+    fn spawn(self: &Rc<Self>) {
+        self.banner.hide();
+        let pane = Rc::downgrade(self);
+        self.terminal.spawn(move |e| {
+            if let Some(pane) = pane.upgrade() {
+                pane.show_stopped(&format!("Failed to start the process: {}", e.message()), true);
+            }
+        });
+    }
+
+    fn setup_child_exit(self: &Rc<Self>) {
+        let pane = Rc::downgrade(self);
+        self.restart_button.connect_clicked(move |_| {
+            if let Some(pane) = pane.upgrade() {
+                pane.spawn();
+                pane.terminal.widget().grab_focus();
+            }
+        });
+
+        let pane = Rc::downgrade(self);
+        self.terminal.widget().connect_child_exited(move |_, status| {
+            let Some(pane) = pane.upgrade() else {
+                return;
+            };
+            if pane.closed.get() {
+                return;
+            }
+            match pane.on_exit {
+                ExitAction::Close => {
+                    let callback = pane.exit_callback.borrow().clone();
+                    if let Some(callback) = callback {
+                        callback();
+                    }
+                }
+                ExitAction::Hold | ExitAction::Restart => {
+                    let (message, failed) = describe_exit_status(status);
+                    pane.show_stopped(&message, failed);
+                }
+            }
+        });
+    }
+
+    /// Tell the user the process is no longer running, offering a restart
+    /// when the profile asks for it.
+    fn show_stopped(&self, message: &str, failed: bool) {
+        self.restart_button.set_visible(self.on_exit == ExitAction::Restart);
+        if failed {
+            self.banner.show_error(message);
+        } else {
+            self.banner.show_info(message);
+        }
+    }
+
+    /// Report a failure that leaves the process running.
+    fn show_error(&self, message: &str) {
+        self.restart_button.set_visible(false);
+        self.banner.show_error(message);
+    }
+
+    /// Called when the process exits and the profile closes the pane.
+    pub fn set_exit_callback<F>(&self, callback: F)
+    where
+        F: Fn() + 'static,
+    {
+        self.exit_callback.replace(Some(Rc::new(callback)));
+    }
+
+    fn setup_context_menu(&self) {
+        let menu = Menu::new();
+
+        let clipboard_section = Menu::new();
+        clipboard_section.append(Some("Copy"), Some("win.copy"));
+        clipboard_section.append(Some("Paste"), Some("win.paste"));
+        clipboard_section.append(Some("Find…"), Some("win.search"));
+        menu.append_section(None, &clipboard_section);
+
+        let split_section = Menu::new();
+        split_section.append(Some("Split Right"), Some("win.split-right"));
+        split_section.append(Some("Split Down"), Some("win.split-down"));
+        split_section.append(Some("Close Pane"), Some("win.close-pane"));
+        menu.append_section(None, &split_section);
+
+        let popover = PopoverMenu::from_model(Some(&menu));
+        popover.set_parent(self.terminal.widget());
+        popover.set_has_arrow(false);
+
+        let gesture = GestureClick::new();
+        gesture.set_button(3); // Right mouse button
+
+        let popover_clone = popover.clone();
+        let terminal = self.terminal.widget().clone();
+        gesture.connect_pressed(move |gesture, _, x, y| {
+            gesture.set_state(gtk4::EventSequenceState::Claimed);
+            // Make the clicked pane the focused one so the actions target it
+            terminal.grab_focus();
+            popover_clone.set_pointing_to(Some(&Rectangle::new(x as i32, y as i32, 1, 1)));
+            popover_clone.popup();
+        });
+
+        self.terminal.widget().add_controller(gesture);
+
+        // Store popover reference for cleanup
+        self.popover.replace(Some(popover));
+    }
+
+    /// Ctrl+click opens the hyperlink or runs the action of the matcher
+    /// under the pointer. The click is claimed before VTE sees it, so it
+    /// does not start a selection.
+    fn setup_matches(self: &Rc<Self>) {
+        let gesture = GestureClick::new();
+        gesture.set_button(1);
+        gesture.set_propagation_phase(PropagationPhase::Capture);
+
+        let pane = Rc::downgrade(self);
+        gesture.connect_pressed(move |gesture, _, x, y| {
+            if !gesture.current_event_state().contains(ModifierType::CONTROL_MASK) {
+                return;
+            }
+            let Some(pane) = pane.upgrade() else {
+                return;
+            };
+            let matched = pane.terminal.match_at(x, y);
+            if let Some(uri) = pane.terminal.hyperlink_at(x, y) {
+                gesture.set_state(gtk4::EventSequenceState::Claimed);
+                // The visible text of a link is only known when it looks like a URL
+                let text = matched.map(|(text, _)| text);
+                let pane_weak = Rc::downgrade(&pane);
+                hyperlink::open(pane.terminal.widget(), &uri, text.as_deref(), move |e| {
+                    if let Some(pane) = pane_weak.upgrade() {
+                        pane.show_error(e);
+                    }
+                });
+                return;
+            }
+            let Some((text, matcher)) = matched else {
+                return;
+            };
+            gesture.set_state(gtk4::EventSequenceState::Claimed);
+            let directory = pane.terminal.current_directory();
+            if let Err(e) = matchers::activate(pane.terminal.widget(), &text, &matcher, directory.as_deref()) {
+                pane.show_error(&e);
+            }
+        });
+
+        self.terminal.widget().add_controller(gesture);
+    }
+
+    /// Show where a hyperlink leads while the pointer is over it.
+    fn setup_link_preview(&self) {
+        let preview = self.link_preview.clone();
+        self.terminal.widget().connect_hyperlink_hover_uri_notify(move |terminal| {
+            match terminal.hyperlink_hover_uri() {
+                Some(uri) => {
+                    preview.set_text(&uri);
+                    preview.set_visible(true);
+                }
+                None => preview.set_visible(false),
+            }
+        });
+    }
+
+    /// Draw a mark for each command next to the terminal, green when it
+    /// succeeded and red when it failed, placed along the scrollback like
+    /// a scrollbar. Hovering a mark describes the command and clicking it
+    /// scrolls to it.
+    fn setup_gutter(self: &Rc<Self>) {
+        let gutter = self.gutter.clone();
+        self.terminal.set_command_callback(move |_| {
+            gutter.set_visible(true);
+            gutter.queue_draw();
+        });
+        if let Some(adjustment) = self.terminal.widget().vadjustment() {
+            let gutter = self.gutter.clone();
+            adjustment.connect_changed(move |_| gutter.queue_draw());
+        }
+
+        let pane = Rc::downgrade(self);
+        self.gutter.set_draw_func(move |_, cr, width, height| {
+            let Some(pane) = pane.upgrade() else {
+                return;
+            };
+            for (y, command) in pane.gutter_marks(height) {
+                if command.status == 0 {
+                    cr.set_source_rgb(0.18, 0.76, 0.49);
+                } else {
+                    cr.set_source_rgb(0.88, 0.11, 0.14);
+                }
+                cr.rectangle(0.0, y - 1.0, f64::from(width), 3.0);
+                let _ = cr.fill();
+            }
+        });
+
+        let pane = Rc::downgrade(self);
+        self.gutter.connect_query_tooltip(move |gutter, _, y, _, tooltip| {
+            let Some(command) = pane.upgrade().and_then(|pane| pane.gutter_mark_at(gutter.height(), y)) else {
+                return false;
+            };
+            tooltip.set_text(Some(&command.summary()));
+            true
+        });
+
+        let gesture = GestureClick::new();
+        let pane = Rc::downgrade(self);
+        gesture.connect_pressed(move |gesture, _, _, y| {
+            let Some(pane) = pane.upgrade() else {
+                return;
+            };
+            if let Some(command) = pane.gutter_mark_at(pane.gutter.height(), y as i32) {
+                gesture.set_state(gtk4::EventSequenceState::Claimed);
+                pane.terminal.scroll_to_command(&command);
+            }
+        });
+        self.gutter.add_controller(gesture);
+    }
+
+    /// Commands with the height of their mark in a `height` pixels strip.
+    fn gutter_marks(&self, height: i32) -> Vec<(f64, Command)> {
+        let Some(adjustment) = self.terminal.widget().vadjustment() else {
+            return Vec::new();
+        };
+        let rows = (adjustment.upper() - adjustment.lower()).max(1.0);
+        self.terminal.commands().into_iter()
+            .map(|command| {
+                let y = (command.prompt_row as f64 - adjustment.lower()) / rows * f64::from(height);
+                (y, command)
+            })
+            .collect()
+    }
+
+    /// The command whose mark is closest to `y`, if it is close enough.
+    fn gutter_mark_at(&self, height: i32, y: i32) -> Option<Command> {
+        let y = f64::from(y);
+        self.gutter_marks(height).into_iter()
+            .map(|(mark_y, command)| ((mark_y - y).abs(), command))
+            .filter(|(distance, _)| *distance <= GUTTER_REACH)
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, command)| command)
+    }
+
+    /// Cleanup resources before destroying the pane
+    pub fn cleanup(&self) {
+        self.closed.set(true);
+        if let Some(popover) = self.popover.take() {
+            popover.unparent();
+        }
+    }
+}
+
+/// Describe a wait status as reported by `child-exited`, and whether it
+/// indicates a failure.
+fn describe_exit_status(status: i32) -> (String, bool) {
+    let signal = status & 0x7f;
+    if signal != 0 {
+        return (format!("The process was killed by signal {}.", signal), true);
+    }
+    let code = (status >> 8) & 0xff;
+    (format!("The process exited with status {}.", code), code != 0)
+}
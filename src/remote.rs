@@ -0,0 +1,294 @@
+//! Remote control over a Unix socket.
+//!
+//! Clients send one JSON request per line and get one JSON response line
+//! back. The socket path is exported to terminals as `RUSTYTERM_LISTEN_ON`,
+//! so scripts running inside rustyterm can find it.
+
+use gtk4::prelude::*;
+use gtk4::{gio, glib};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::OnceLock;
+
+use crate::pane::Pane;
+use crate::tab::{Tab, TabOptions};
+use crate::theme::get_theme_by_name;
+use crate::window::RustyTermWindow;
+
+/// Environment variable holding the socket path in child processes.
+pub const LISTEN_ON_VAR: &str = "RUSTYTERM_LISTEN_ON";
+
+static SOCKET_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Path of the socket, once [`RemoteControl::listen`] succeeded.
+pub fn socket_path() -> Option<&'static Path> {
+    SOCKET_PATH.get().map(PathBuf::as_path)
+}
+
+/// A request read from the socket. Targets default to the focused window,
+/// tab or pane when their id is omitted.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+enum Request {
+    /// Windows with their tabs and panes
+    List,
+    SendText {
+        pane: Option<u32>,
+        text: String,
+    },
+    GetText {
+        pane: Option<u32>,
+        /// Whole scrollback instead of the visible screen
+        #[serde(default)]
+        scrollback: bool,
+    },
+    /// Commands reported by the shell integration, oldest first
+    GetCommands {
+        pane: Option<u32>,
+    },
+    /// Fix a tab title, or go back to the terminal's title with `null`
+    SetTabTitle {
+        tab: Option<u32>,
+        title: Option<String>,
+    },
+    SetTheme {
+        theme: String,
+    },
+    NewTab {
+        window: Option<u32>,
+        command: Option<Vec<String>>,
+        working_directory: Option<PathBuf>,
+        profile: Option<String>,
+        title: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// The listening socket of this instance.
+pub struct RemoteControl {
+    service: gio::SocketService,
+    path: PathBuf,
+}
+
+impl RemoteControl {
+    /// Listen on a socket in the user's runtime directory and serve
+    /// requests against `windows`.
+    pub fn listen(windows: Rc<RefCell<Vec<Rc<RustyTermWindow>>>>) -> Result<Self, glib::Error> {
+        let path = glib::user_runtime_dir().join(format!("rustyterm-{}.sock", std::process::id()));
+        // A socket left behind by a crashed process with the same pid
+        let _ = fs::remove_file(&path);
+
+        let service = gio::SocketService::new();
+        service.add_address(
+            &gio::UnixSocketAddress::new(&path),
+            gio::SocketType::Stream,
+            gio::SocketProtocol::Default,
+            None::<&glib::Object>,
+        )?;
+
+        service.connect_incoming(move |_, connection, _| {
+            let connection = connection.clone();
+            let windows = windows.clone();
+            glib::MainContext::default().spawn_local(async move {
+                if let Err(e) = serve(&connection, &windows).await {
+                    eprintln!("Remote control connection failed: {}", e);
+                }
+            });
+            true
+        });
+        service.start();
+
+        let _ = SOCKET_PATH.set(path.clone());
+        Ok(Self { service, path })
+    }
+
+    /// Stop listening and remove the socket file.
+    pub fn stop(&self) {
+        self.service.stop();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Answer requests on a connection until the client closes it.
+async fn serve(
+    connection: &gio::SocketConnection,
+    windows: &Rc<RefCell<Vec<Rc<RustyTermWindow>>>>,
+) -> Result<(), glib::Error> {
+    let input = gio::DataInputStream::new(&connection.input_stream());
+    let output = connection.output_stream();
+
+    while let Some(line) = input.read_line_utf8_future(glib::Priority::DEFAULT).await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => match handle(request, &windows.borrow()) {
+                Ok(data) => Response { ok: true, data: Some(data), error: None },
+                Err(error) => Response { ok: false, data: None, error: Some(error) },
+            },
+            Err(e) => Response {
+                ok: false,
+                data: None,
+                error: Some(format!("Invalid request: {}", e)),
+            },
+        };
+
+        let mut bytes = serde_json::to_vec(&response).unwrap_or_default();
+        bytes.push(b'\n');
+        output
+            .write_all_future(bytes, glib::Priority::DEFAULT)
+            .await
+            .map_err(|(_, e)| e)?;
+    }
+    Ok(())
+}
+
+fn handle(request: Request, windows: &[Rc<RustyTermWindow>]) -> Result<Value, String> {
+    match request {
+        Request::List => Ok(Value::Array(windows.iter().map(|w| describe_window(w)).collect())),
+        Request::SendText { pane, text } => {
+            find_pane(windows, pane)?.terminal.send_text(&text);
+            Ok(Value::Null)
+        }
+        Request::GetText { pane, scrollback } => {
+            let text = find_pane(windows, pane)?
+                .terminal
+                .text(scrollback)
+                .map_err(|e| format!("Cannot read terminal text: {}", e))?;
+            Ok(Value::String(text))
+        }
+        Request::GetCommands { pane } => {
+            let commands: Vec<Value> = find_pane(windows, pane)?
+                .terminal
+                .commands()
+                .into_iter()
+                .map(|command| json!({
+                    "command": command.command,
+                    "status": command.status,
+                    "duration_ms": command.duration.map(|duration| duration.as_millis() as u64),
+                }))
+                .collect();
+            Ok(Value::Array(commands))
+        }
+        Request::SetTabTitle { tab, title } => {
+            find_tab(windows, tab)?.borrow().set_custom_title(title);
+            Ok(Value::Null)
+        }
+        Request::SetTheme { theme } => {
+            if get_theme_by_name(&theme).is_none() {
+                return Err(format!("Unknown theme \"{}\"", theme));
+            }
+            // The action applies the theme to every window and saves it
+            let window = focused_window(windows)?;
+            ActionGroupExt::activate_action(&window.window, "set-theme", Some(&theme.to_variant()));
+            Ok(Value::Null)
+        }
+        Request::NewTab { window, command, working_directory, profile, title } => {
+            let window = match window {
+                Some(id) => windows
+                    .iter()
+                    .find(|w| w.id == id)
+                    .ok_or_else(|| format!("No window with id {}", id))?,
+                None => focused_window(windows)?,
+            };
+            if let Some(profile) = &profile
+                && !window.has_profile(profile)
+            {
+                return Err(format!("Unknown profile \"{}\"", profile));
+            }
+            let options = TabOptions {
+                profile,
+                command,
+                working_directory,
+                title,
+                ..TabOptions::default()
+            };
+            let tab = window.add_tab(&options);
+            let tab = tab.borrow();
+            Ok(json!({ "tab": tab.id, "pane": tab.active_pane().id }))
+        }
+    }
+}
+
+fn describe_window(window: &RustyTermWindow) -> Value {
+    let current = window.current();
+    let tabs: Vec<Value> = window
+        .tabs()
+        .iter()
+        .map(|tab| {
+            let is_current = current.as_ref().is_some_and(|c| Rc::ptr_eq(c, tab));
+            let tab = tab.borrow();
+            let active = tab.active_pane();
+            let panes: Vec<Value> = tab
+                .panes()
+                .iter()
+                .map(|pane| {
+                    json!({
+                        "id": pane.id,
+                        "profile": pane.terminal.profile_name(),
+                        "working_directory": pane.terminal.current_directory(),
+                        "focused": Rc::ptr_eq(pane, &active),
+                    })
+                })
+                .collect();
+            json!({
+                "id": tab.id,
+                "title": tab.title_label.text().as_str(),
+                "current": is_current,
+                "panes": panes,
+            })
+        })
+        .collect();
+
+    json!({
+        "id": window.id,
+        "focused": window.window.is_active(),
+        "tabs": tabs,
+    })
+}
+
+/// The focused window, or the most recently opened one.
+fn focused_window(windows: &[Rc<RustyTermWindow>]) -> Result<&Rc<RustyTermWindow>, String> {
+    windows
+        .iter()
+        .find(|w| w.window.is_active())
+        .or_else(|| windows.last())
+        .ok_or_else(|| "No window is open".to_string())
+}
+
+fn find_tab(windows: &[Rc<RustyTermWindow>], id: Option<u32>) -> Result<Rc<RefCell<Tab>>, String> {
+    match id {
+        Some(id) => windows
+            .iter()
+            .flat_map(|w| w.tabs())
+            .find(|tab| tab.borrow().id == id)
+            .ok_or_else(|| format!("No tab with id {}", id)),
+        None => focused_window(windows)?
+            .current()
+            .ok_or_else(|| "No tab is open".to_string()),
+    }
+}
+
+fn find_pane(windows: &[Rc<RustyTermWindow>], id: Option<u32>) -> Result<Rc<Pane>, String> {
+    match id {
+        Some(id) => windows
+            .iter()
+            .flat_map(|w| w.tabs())
+            .find_map(|tab| tab.borrow().panes().iter().find(|pane| pane.id == id).cloned())
+            .ok_or_else(|| format!("No pane with id {}", id)),
+        None => Ok(find_tab(windows, None)?.borrow().active_pane()),
+    }
+}
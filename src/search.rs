@@ -0,0 +1,367 @@
+//! Scrollback search.
+//!
+//! Each tab has a search bar floating over its terminals. It searches the
+//! focused pane with VTE's own regex engine, which highlights the match
+//! and scrolls to it.
+
+use gtk4::prelude::*;
+use gtk4::{Align, Box, Button, Label, Orientation, Revealer, RevealerTransitionType, SearchEntry, ToggleButton};
+use vte4::{Terminal, TerminalExt};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::terminal::scrollback_text;
+
+// PCRE2 options, which VTE takes as plain flags
+const PCRE2_NOTEMPTY: u32 = 0x0000_0004;
+const PCRE2_CASELESS: u32 = 0x0000_0008;
+pub const PCRE2_MULTILINE: u32 = 0x0000_0400;
+pub const PCRE2_UCP: u32 = 0x0002_0000;
+pub const PCRE2_UTF: u32 = 0x0008_0000;
+const PCRE2_SUBSTITUTE_GLOBAL: u32 = 0x0000_0100;
+
+/// Marks each match when counting them. Control characters are never
+/// part of the terminal's text.
+pub const MATCH_MARKER: char = '\u{1}';
+
+pub struct SearchBar {
+    pub revealer: Revealer,
+    entry: SearchEntry,
+    options: SearchOptions,
+    status: Label,
+    /// Terminal being searched, while the bar is open
+    terminal: RefCell<Option<Terminal>>,
+    /// Text of that terminal, dumped on the first search and dropped when
+    /// its contents change
+    snapshot: Rc<RefCell<Option<String>>>,
+    /// Handler dropping the snapshot, connected to the searched terminal
+    contents_handler: RefCell<Option<glib::SignalHandlerId>>,
+}
+
+impl SearchBar {
+    pub fn new() -> Rc<Self> {
+        let entry = SearchEntry::new();
+        entry.set_placeholder_text(Some("Find"));
+        entry.set_width_chars(24);
+
+        let options = SearchOptions::new();
+
+        let previous_button = Button::from_icon_name("go-up-symbolic");
+        previous_button.set_tooltip_text(Some("Previous match"));
+        let next_button = Button::from_icon_name("go-down-symbolic");
+        next_button.set_tooltip_text(Some("Next match"));
+
+        let status = Label::new(None);
+        status.set_width_chars(10);
+        status.set_max_width_chars(30);
+        status.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+        status.set_xalign(0.0);
+
+        let close_button = Button::from_icon_name("window-close-symbolic");
+        close_button.set_has_frame(false);
+        close_button.set_tooltip_text(Some("Close"));
+
+        let content = Box::new(Orientation::Horizontal, 4);
+        content.add_css_class("search-bar");
+        content.add_css_class("osd");
+        for widget in [
+            entry.upcast_ref::<gtk4::Widget>(),
+            options.case_sensitive.upcast_ref(),
+            options.whole_word.upcast_ref(),
+            options.regex.upcast_ref(),
+            previous_button.upcast_ref(),
+            next_button.upcast_ref(),
+            status.upcast_ref(),
+            close_button.upcast_ref(),
+        ] {
+            content.append(widget);
+        }
+
+        let revealer = Revealer::new();
+        revealer.set_transition_type(RevealerTransitionType::SlideDown);
+        revealer.set_halign(Align::End);
+        revealer.set_valign(Align::Start);
+        revealer.set_child(Some(&content));
+
+        let bar = Rc::new(Self {
+            revealer,
+            entry,
+            options,
+            status,
+            terminal: RefCell::new(None),
+            snapshot: Rc::new(RefCell::new(None)),
+            contents_handler: RefCell::new(None),
+        });
+
+        let bar_weak = Rc::downgrade(&bar);
+        bar.entry.connect_search_changed(move |_| {
+            if let Some(bar) = bar_weak.upgrade() {
+                bar.update();
+            }
+        });
+        let bar_weak = Rc::downgrade(&bar);
+        bar.options.connect_changed(move || {
+            if let Some(bar) = bar_weak.upgrade() {
+                bar.update();
+            }
+        });
+
+        // The newest output is at the bottom, so Enter searches upwards
+        let find = bar.finder(false);
+        bar.entry.connect_activate(move |_| find());
+        let find = bar.finder(false);
+        bar.entry.connect_previous_match(move |_| find());
+        let find = bar.finder(false);
+        previous_button.connect_clicked(move |_| find());
+        let find = bar.finder(true);
+        bar.entry.connect_next_match(move |_| find());
+        let find = bar.finder(true);
+        next_button.connect_clicked(move |_| find());
+
+        let bar_weak = Rc::downgrade(&bar);
+        bar.entry.connect_stop_search(move |_| {
+            if let Some(bar) = bar_weak.upgrade() {
+                bar.close();
+            }
+        });
+        let bar_weak = Rc::downgrade(&bar);
+        close_button.connect_clicked(move |_| {
+            if let Some(bar) = bar_weak.upgrade() {
+                bar.close();
+            }
+        });
+
+        bar
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.revealer.reveals_child()
+    }
+
+    /// Show the bar searching `terminal`, with the entry focused.
+    pub fn open(&self, terminal: &Terminal) {
+        self.set_terminal(terminal);
+        self.revealer.set_reveal_child(true);
+        self.entry.grab_focus();
+        self.entry.select_region(0, -1);
+    }
+
+    /// Search `terminal` instead of the current one, such as when another
+    /// pane of the tab gets focus.
+    pub fn set_terminal(&self, terminal: &Terminal) {
+        let previous = self.terminal.replace(Some(terminal.clone()));
+        if previous.as_ref() == Some(terminal) {
+            return;
+        }
+        if let Some(previous) = previous {
+            previous.search_set_regex(None, 0);
+            self.forget_snapshot(&previous);
+        }
+        let snapshot = self.snapshot.clone();
+        let handler = terminal.connect_contents_changed(move |_| {
+            snapshot.take();
+        });
+        self.contents_handler.replace(Some(handler));
+        self.update();
+    }
+
+    /// Hide the bar, remove the highlight and give focus back to the terminal.
+    pub fn close(&self) {
+        self.revealer.set_reveal_child(false);
+        if let Some(terminal) = self.terminal.take() {
+            terminal.search_set_regex(None, 0);
+            self.forget_snapshot(&terminal);
+            terminal.grab_focus();
+        }
+    }
+
+    /// Drop the text of `terminal`, which is no longer searched.
+    fn forget_snapshot(&self, terminal: &Terminal) {
+        if let Some(handler) = self.contents_handler.take() {
+            terminal.disconnect(handler);
+        }
+        self.snapshot.take();
+    }
+
+    /// Number of matches in the text of `terminal`, reusing its snapshot
+    /// while the output does not change.
+    fn count_matches(&self, terminal: &Terminal, regex: &vte4::Regex) -> Option<usize> {
+        let mut snapshot = self.snapshot.borrow_mut();
+        if snapshot.is_none() {
+            *snapshot = scrollback_text(terminal).ok();
+        }
+        let marked = mark_matches(regex, snapshot.as_ref()?)?;
+        Some(marked.matches(MATCH_MARKER).count())
+    }
+
+    /// Apply the entry and toggles to the terminal and jump to the closest
+    /// match above the bottom.
+    fn update(&self) {
+        self.status.remove_css_class("error");
+        self.status.set_text("");
+        self.status.set_tooltip_text(None);
+        let Some(terminal) = self.terminal.borrow().clone() else {
+            return;
+        };
+
+        let regex = match self.options.query(&self.entry.text()).compile() {
+            Ok(Some(regex)) => regex,
+            Ok(None) => {
+                terminal.search_set_regex(None, 0);
+                return;
+            }
+            Err(e) => {
+                terminal.search_set_regex(None, 0);
+                self.status.add_css_class("error");
+                self.status.set_text(e.message());
+                self.status.set_tooltip_text(Some(e.message()));
+                return;
+            }
+        };
+        terminal.search_set_regex(Some(&regex), 0);
+        terminal.search_set_wrap_around(true);
+
+        let count = self.count_matches(&terminal, &regex);
+        self.status.set_text(&count.map(describe_count).unwrap_or_default());
+        terminal.search_find_previous();
+    }
+
+    /// Callback moving to the next match below, or above unless `forward`.
+    fn finder(self: &Rc<Self>, forward: bool) -> impl Fn() + 'static {
+        let bar_weak = Rc::downgrade(self);
+        move || {
+            let Some(bar) = bar_weak.upgrade() else {
+                return;
+            };
+            if let Some(terminal) = bar.terminal.borrow().as_ref() {
+                if forward {
+                    terminal.search_find_next();
+                } else {
+                    terminal.search_find_previous();
+                }
+            }
+        }
+    }
+}
+
+/// What to search for, and how.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    pub text: String,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Use `text` as a regex instead of looking for it literally
+    pub regex: bool,
+}
+
+impl SearchQuery {
+    /// Compile the query with the options VTE searches with, or `None`
+    /// when there is nothing to look for.
+    pub fn compile(&self) -> Result<Option<vte4::Regex>, glib::Error> {
+        if self.text.is_empty() {
+            return Ok(None);
+        }
+        let mut flags = PCRE2_UTF | PCRE2_UCP | PCRE2_MULTILINE;
+        if !self.case_sensitive {
+            flags |= PCRE2_CASELESS;
+        }
+        let pattern = search_pattern(&self.text, self.whole_word, self.regex);
+        vte4::Regex::for_search(&pattern, flags).map(Some)
+    }
+}
+
+/// Toggle buttons for the case, whole word and regex options.
+pub struct SearchOptions {
+    pub case_sensitive: ToggleButton,
+    pub whole_word: ToggleButton,
+    pub regex: ToggleButton,
+}
+
+impl SearchOptions {
+    pub fn new() -> Self {
+        Self {
+            case_sensitive: toggle("Aa", "Match case"),
+            whole_word: toggle("W", "Match whole words"),
+            regex: toggle(".*", "Regular expression"),
+        }
+    }
+
+    /// The query for `text` with the options currently selected.
+    pub fn query(&self, text: &str) -> SearchQuery {
+        SearchQuery {
+            text: text.to_string(),
+            case_sensitive: self.case_sensitive.is_active(),
+            whole_word: self.whole_word.is_active(),
+            regex: self.regex.is_active(),
+        }
+    }
+
+    /// Call `callback` whenever an option is toggled.
+    pub fn connect_changed<F: Fn() + 'static>(&self, callback: F) {
+        let callback = Rc::new(callback);
+        for button in [&self.case_sensitive, &self.whole_word, &self.regex] {
+            let callback = callback.clone();
+            button.connect_toggled(move |_| callback());
+        }
+    }
+}
+
+fn toggle(label: &str, tooltip: &str) -> ToggleButton {
+    let button = ToggleButton::with_label(label);
+    button.set_tooltip_text(Some(tooltip));
+    button
+}
+
+/// Regex for what was typed: escaped unless it is a regex already, and
+/// bounded by word boundaries for whole-word searches.
+fn search_pattern(text: &str, whole_word: bool, regex: bool) -> String {
+    let pattern = if regex {
+        text.to_string()
+    } else {
+        glib::Regex::escape_string(text).to_string()
+    };
+    if whole_word {
+        format!("\\b(?:{})\\b", pattern)
+    } else {
+        pattern
+    }
+}
+
+/// `text` with [`MATCH_MARKER`] before every match, found with the regex
+/// VTE highlights with so counts and results always agree with it. Like
+/// VTE, this skips empty matches, such as those of `x*`, `^` or `\b`.
+pub fn mark_matches(regex: &vte4::Regex, text: &str) -> Option<String> {
+    let replacement = format!("{}$0", MATCH_MARKER);
+    regex.substitute(text, &replacement, PCRE2_SUBSTITUTE_GLOBAL | PCRE2_NOTEMPTY)
+        .map(|marked| marked.to_string())
+        .ok()
+}
+
+pub fn describe_count(count: usize) -> String {
+    match count {
+        0 => "No matches".to_string(),
+        1 => "1 match".to_string(),
+        count => format!("{} matches", count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_is_escaped() {
+        assert_eq!(search_pattern("a.b", false, false), "a\\.b");
+        assert_eq!(search_pattern("a.b", false, true), "a.b");
+        assert_eq!(search_pattern("foo|bar", true, true), "\\b(?:foo|bar)\\b");
+    }
+
+    #[test]
+    fn empty_matches_are_not_marked() {
+        let query = |text: &str| SearchQuery { text: text.to_string(), regex: true, ..Default::default() };
+        let marked = |text: &str| mark_matches(&query(text).compile().unwrap().unwrap(), "axxb\nx").unwrap();
+        assert_eq!(marked("x*"), "a\u{1}xxb\n\u{1}x");
+        assert_eq!(marked("^"), "axxb\nx");
+        assert_eq!(marked("\\b"), "axxb\nx");
+    }
+}
@@ -0,0 +1,243 @@
+//! Saved windows, tabs and splits.
+//!
+//! A session is written as JSON to `sessions/<name>.json` in the config
+//! directory when the last window closes or rustyterm quits, and reopened
+//! at the next start or with `rustyterm --session <name>`. With
+//! `remember_window_size`, the size of the last window closed is kept in
+//! `window.json` next to them.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::{write_atomic, Config};
+
+/// Session used when none is named on the command line.
+pub const DEFAULT_SESSION: &str = "default";
+
+/// Every window that was open, in the order they were opened.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub windows: Vec<WindowState>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowState {
+    pub width: i32,
+    pub height: i32,
+    pub maximized: bool,
+    pub fullscreen: bool,
+    /// Tabs in the order they are shown
+    pub tabs: Vec<TabState>,
+    /// Index of the tab that was shown
+    pub current_tab: usize,
+}
+
+/// Size and state of the last window closed, which new windows reuse when
+/// `remember_window_size` is set. Kept apart from config.toml so that
+/// closing a window never rewrites the user's settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowGeometry {
+    pub width: i32,
+    pub height: i32,
+    pub maximized: bool,
+    pub fullscreen: bool,
+    /// Columns and rows, saved when the config sizes windows that way
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grid: Option<(i64, i64)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TabState {
+    /// Title fixed with `--title` or remote control
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub layout: Layout,
+}
+
+/// The split tree of a tab.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Layout {
+    Pane {
+        profile: String,
+        /// Last directory reported by the shell
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        working_directory: Option<PathBuf>,
+    },
+    Split {
+        /// Where `second` sits relative to `first`
+        direction: SplitDirection,
+        /// Size of `first` in pixels
+        position: i32,
+        first: Box<Layout>,
+        second: Box<Layout>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitDirection {
+    Right,
+    Down,
+}
+
+impl Layout {
+    pub fn split(direction: SplitDirection, position: i32, first: Layout, second: Layout) -> Self {
+        Layout::Split {
+            direction,
+            position,
+            first: Box::new(first),
+            second: Box::new(second),
+        }
+    }
+}
+
+/// Error raised while reading or writing a session file.
+#[derive(Debug)]
+pub enum SessionError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SessionError::Parse { path, source } => write!(f, "{}: {}", path.display(), source),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+impl SessionError {
+    fn io(path: &Path, source: io::Error) -> Self {
+        SessionError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+/// Session names become file names, so they cannot contain a path.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains('/')
+}
+
+impl Session {
+    pub fn file(name: &str) -> PathBuf {
+        Config::config_dir().join("sessions").join(format!("{}.json", name))
+    }
+
+    /// Read the named session, or `None` if it was never saved.
+    pub fn load(name: &str) -> Result<Option<Self>, SessionError> {
+        read_json(&Self::file(name))
+    }
+
+    pub fn save(&self, name: &str) -> Result<(), SessionError> {
+        write_json(&Self::file(name), self)
+    }
+}
+
+impl WindowGeometry {
+    pub fn file() -> PathBuf {
+        Config::config_dir().join("window.json")
+    }
+
+    /// Read the remembered geometry, or `None` if no window saved it yet.
+    pub fn load() -> Result<Option<Self>, SessionError> {
+        read_json(&Self::file())
+    }
+
+    pub fn save(&self) -> Result<(), SessionError> {
+        write_json(&Self::file(), self)
+    }
+}
+
+/// Parse a JSON state file, or return `None` if it does not exist.
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, SessionError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(SessionError::io(path, e)),
+    };
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|source| SessionError::Parse { path: path.to_path_buf(), source })
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), SessionError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| SessionError::io(dir, e))?;
+    }
+    let content = serde_json::to_string_pretty(value).map_err(|e| SessionError::io(path, e.into()))?;
+    write_atomic(path, &content).map_err(|e| SessionError::io(path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_round_trips() {
+        let session = Session {
+            windows: vec![WindowState {
+                width: 800,
+                height: 600,
+                maximized: true,
+                fullscreen: false,
+                current_tab: 0,
+                tabs: vec![TabState {
+                    title: Some("build".to_string()),
+                    layout: Layout::split(
+                        SplitDirection::Right,
+                        400,
+                        Layout::Pane {
+                            profile: "Default".to_string(),
+                            working_directory: Some(PathBuf::from("/tmp")),
+                        },
+                        Layout::Pane {
+                            profile: "Server".to_string(),
+                            working_directory: None,
+                        },
+                    ),
+                }],
+            }],
+        };
+        let json = serde_json::to_string(&session).unwrap();
+        assert_eq!(serde_json::from_str::<Session>(&json).unwrap(), session);
+    }
+
+    #[test]
+    fn geometry_round_trips() {
+        let geometry = WindowGeometry { width: 1024, height: 768, maximized: false, fullscreen: true, grid: None };
+        let json = serde_json::to_string(&geometry).unwrap();
+        assert!(!json.contains("grid"));
+        assert_eq!(serde_json::from_str::<WindowGeometry>(&json).unwrap(), geometry);
+
+        let geometry = WindowGeometry { grid: Some((120, 40)), ..geometry };
+        let json = serde_json::to_string(&geometry).unwrap();
+        assert_eq!(serde_json::from_str::<WindowGeometry>(&json).unwrap(), geometry);
+    }
+
+    #[test]
+    fn names_cannot_be_paths() {
+        assert!(is_valid_name("work"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("../config"));
+        assert!(!is_valid_name(".hidden"));
+    }
+}
@@ -0,0 +1,370 @@
+//! Shell integration.
+//!
+//! rustyterm loads scripts into bash, zsh and fish that mark each prompt
+//! and command with the FinalTerm (OSC 133) sequences. VTE does not report
+//! those marks to applications, so the scripts repeat each one as an OSC 6
+//! URI, which VTE passes on as `current-file-uri`:
+//!
+//! - `file://rustyterm/SERIAL/prompt`: a prompt is shown
+//! - `file://rustyterm/SERIAL/start/COMMAND`: a command starts
+//! - `file://rustyterm/SERIAL/finish/STATUS/COMMAND`: it ended with exit
+//!   status `STATUS`, and the next prompt is shown
+//!
+//! `COMMAND` is percent-encoded, and empty when bash did not save it to
+//! its history. The serial makes every mark differ from the previous one.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::terminal::percent_decode;
+
+const BASH_SCRIPT: &str = include_str!("../data/shell-integration/rustyterm.bash");
+const ZSH_ENV: &str = include_str!("../data/shell-integration/zshenv");
+const ZSH_SCRIPT: &str = include_str!("../data/shell-integration/rustyterm.zsh");
+const FISH_SCRIPT: &str = include_str!("../data/shell-integration/rustyterm.fish");
+
+/// Prefix of the URIs the scripts send.
+const MARK_PREFIX: &str = "file://rustyterm/";
+
+/// A mark sent by the scripts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mark {
+    /// A prompt is shown, and no command ran since the last one
+    Prompt,
+    Start { command: String },
+    /// `command` ended, and the next prompt is shown
+    Finish { command: String, status: i32 },
+}
+
+/// Read a mark from the terminal's `current-file-uri`.
+pub fn parse_mark(uri: &str) -> Option<Mark> {
+    let mut parts = uri.strip_prefix(MARK_PREFIX)?.split('/');
+    let _serial = parts.next()?;
+    let decode = |part: Option<&str>| {
+        let bytes = percent_decode(part.unwrap_or_default())?;
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    };
+    match parts.next()? {
+        "prompt" => Some(Mark::Prompt),
+        "start" => Some(Mark::Start { command: decode(parts.next())? }),
+        "finish" => {
+            let status = parts.next()?.parse().ok()?;
+            Some(Mark::Finish { command: decode(parts.next())?, status })
+        }
+        _ => None,
+    }
+}
+
+/// A command that ran in a terminal. Rows count from the top of the
+/// terminal's buffer, scrollback included, as VTE's cursor position does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Command {
+    pub command: String,
+    pub status: i32,
+    /// Time from its start to its end, unless the start was not seen
+    pub duration: Option<Duration>,
+    /// Row of the prompt it was entered at
+    pub prompt_row: i64,
+    /// First row of its output
+    pub output_row: i64,
+    /// Row of the next prompt, where its output ends
+    pub end_row: i64,
+}
+
+impl Command {
+    /// The command line with how it ended, for tooltips.
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{}\nExit status {}", self.command, self.status);
+        if let Some(duration) = self.duration {
+            summary.push_str(&format!(" after {}", describe_duration(duration)));
+        }
+        summary
+    }
+}
+
+fn describe_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64();
+    if seconds < 1.0 {
+        format!("{} ms", duration.as_millis())
+    } else if seconds < 60.0 {
+        format!("{:.1} s", seconds)
+    } else {
+        let seconds = duration.as_secs();
+        format!("{} min {} s", seconds / 60, seconds % 60)
+    }
+}
+
+/// Prompts and commands of one terminal, built from its marks.
+#[derive(Debug, Default)]
+pub struct History {
+    /// Rows of the prompts, in order
+    prompts: Vec<i64>,
+    commands: Vec<Command>,
+    /// Start time of the running command
+    started: Option<Instant>,
+}
+
+impl History {
+    /// Take in `mark`, reported with the cursor on `row` at `now`, and
+    /// return the command it finished, if any.
+    ///
+    /// VTE reports marks once it has processed the output around them, so
+    /// the cursor is on the last line of a prompt, where the command is
+    /// typed. Output following a start mark can arrive with it, so its
+    /// position is not used.
+    pub fn record(&mut self, mark: Mark, row: i64, now: Instant) -> Option<&Command> {
+        match mark {
+            Mark::Prompt => {
+                self.started = None;
+                self.add_prompt(row);
+                None
+            }
+            Mark::Start { .. } => {
+                self.started = Some(now);
+                None
+            }
+            Mark::Finish { command, status } => {
+                let duration = self.started.take().map(|started| now.duration_since(started));
+                let prompt_row = self.prompts.last().copied().filter(|prompt| *prompt < row);
+                // Output starts below the lines of the command
+                let output_row = prompt_row
+                    .map_or(row, |prompt| prompt + command.lines().count().max(1) as i64)
+                    .min(row);
+                self.add_prompt(row);
+                self.commands.push(Command {
+                    command,
+                    status,
+                    duration,
+                    prompt_row: prompt_row.unwrap_or(output_row),
+                    output_row,
+                    end_row: row,
+                });
+                self.commands.last()
+            }
+        }
+    }
+
+    fn add_prompt(&mut self, row: i64) {
+        // Rows start over after a reset, which leaves older marks meaningless
+        if self.prompts.last().is_some_and(|last| *last >= row) {
+            self.prompts.retain(|prompt| *prompt < row);
+            self.commands.retain(|command| command.end_row < row);
+        }
+        self.prompts.push(row);
+    }
+
+    /// Forget what scrolled out of the scrollback, above `first_row`.
+    pub fn forget_before(&mut self, first_row: i64) {
+        self.prompts.retain(|prompt| *prompt >= first_row);
+        self.commands.retain(|command| command.end_row >= first_row);
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// The closest prompt above `row`.
+    pub fn prompt_before(&self, row: i64) -> Option<i64> {
+        self.prompts.iter().rev().copied().find(|prompt| *prompt < row)
+    }
+
+    /// The closest prompt below `row`.
+    pub fn prompt_after(&self, row: i64) -> Option<i64> {
+        self.prompts.iter().copied().find(|prompt| *prompt > row)
+    }
+}
+
+/// Shells the scripts are written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    fn from_program(program: &str) -> Option<Self> {
+        match Path::new(program).file_name()?.to_str()? {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            _ => None,
+        }
+    }
+}
+
+/// Make the shell started with `argv` load its integration script.
+/// `envv` holds the `NAME=value` entries VTE adds to the environment.
+/// Shells given arguments, such as `bash -c`, are left alone.
+pub fn inject(program: &str, argv: &mut Vec<String>, envv: &mut Vec<String>) {
+    if argv.len() != 1 || Shell::from_program(program).is_none() {
+        return;
+    }
+    let Some(dir) = install() else {
+        return;
+    };
+    let inherited = |name: &str| {
+        let prefix = format!("{}=", name);
+        match envv.iter().rev().find(|entry| entry.starts_with(&prefix) || *entry == name) {
+            Some(entry) => entry.strip_prefix(&prefix).map(String::from),
+            None => std::env::var(name).ok(),
+        }
+    };
+    let added = inject_into(dir, program, argv, inherited);
+    envv.extend(added);
+}
+
+/// The work of [`inject`] with scripts installed in `dir`, returning the
+/// entries to add to the environment. `inherited` gives the value the
+/// shell would otherwise see for a variable.
+fn inject_into<F>(dir: &Path, program: &str, argv: &mut Vec<String>, inherited: F) -> Vec<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let dir = dir.to_string_lossy();
+    let mut env = Vec::new();
+    match Shell::from_program(program) {
+        Some(Shell::Bash) => {
+            if argv[0].starts_with('-') {
+                // Login shells ignore --init-file; the script reads the
+                // login files instead
+                argv[0] = argv[0].trim_start_matches('-').to_string();
+                env.push("RUSTYTERM_BASH_LOGIN=1".to_string());
+            }
+            argv.push("--init-file".to_string());
+            argv.push(format!("{}/rustyterm.bash", dir));
+        }
+        Some(Shell::Zsh) => {
+            if let Some(zdotdir) = inherited("ZDOTDIR") {
+                env.push(format!("RUSTYTERM_ZDOTDIR={}", zdotdir));
+            }
+            env.push(format!("ZDOTDIR={}/zsh", dir));
+            env.push(format!("RUSTYTERM_SHELL_INTEGRATION={}/zsh", dir));
+        }
+        Some(Shell::Fish) => {
+            match inherited("XDG_DATA_DIRS").filter(|dirs| !dirs.is_empty()) {
+                Some(dirs) => {
+                    env.push(format!("XDG_DATA_DIRS={}:{}", dir, dirs));
+                    env.push(format!("RUSTYTERM_XDG_DATA_DIRS={}", dirs));
+                }
+                None => env.push(format!("XDG_DATA_DIRS={}", dir)),
+            }
+        }
+        None => {}
+    }
+    env
+}
+
+/// Write the scripts to the runtime directory, once. Returns where they
+/// are, or `None` if they could not be written.
+fn install() -> Option<&'static Path> {
+    static DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = glib::user_runtime_dir().join("rustyterm").join("shell-integration");
+        let files = [
+            ("rustyterm.bash", BASH_SCRIPT),
+            ("zsh/.zshenv", ZSH_ENV),
+            ("zsh/rustyterm.zsh", ZSH_SCRIPT),
+            ("fish/vendor_conf.d/rustyterm.fish", FISH_SCRIPT),
+        ];
+        for (name, contents) in files {
+            let path = dir.join(name);
+            let result = path.parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&path, contents));
+            if let Err(e) = result {
+                eprintln!("Failed to install the shell integration to {}: {}", path.display(), e);
+                return None;
+            }
+        }
+        Some(dir)
+    })
+    .as_deref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finish(command: &str, status: i32) -> Mark {
+        Mark::Finish { command: command.to_string(), status }
+    }
+
+    #[test]
+    fn marks_are_read_from_uris() {
+        assert_eq!(parse_mark("file://rustyterm/1/prompt"), Some(Mark::Prompt));
+        assert_eq!(
+            parse_mark("file://rustyterm/2/start/ls%20-l%2Ftmp"),
+            Some(Mark::Start { command: "ls -l/tmp".to_string() })
+        );
+        assert_eq!(parse_mark("file://rustyterm/3/finish/1/false"), Some(finish("false", 1)));
+        assert_eq!(parse_mark("file://rustyterm/3/finish/0/"), Some(finish("", 0)));
+        assert_eq!(parse_mark("file:///home/me/notes.txt"), None);
+        assert_eq!(parse_mark("file://rustyterm/4/finish/x/false"), None);
+    }
+
+    #[test]
+    fn commands_span_from_their_prompt_to_the_next() {
+        let mut history = History::default();
+        let now = Instant::now();
+        history.record(Mark::Prompt, 0, now);
+        history.record(Mark::Start { command: "ls".to_string() }, 1, now);
+        let command = history.record(finish("ls", 0), 5, now + Duration::from_secs(2)).unwrap();
+        assert_eq!((command.prompt_row, command.output_row, command.end_row), (0, 1, 5));
+        assert_eq!(command.duration, Some(Duration::from_secs(2)));
+
+        // The start mark arrived together with the finish mark
+        let command = history.record(finish("true", 0), 6, now).unwrap();
+        assert_eq!((command.prompt_row, command.output_row, command.duration), (5, 6, None));
+
+        assert_eq!(history.prompt_before(5), Some(0));
+        assert_eq!(history.prompt_after(0), Some(5));
+        assert_eq!(history.prompt_after(6), None);
+    }
+
+    #[test]
+    fn durations_are_readable() {
+        assert_eq!(describe_duration(Duration::from_millis(350)), "350 ms");
+        assert_eq!(describe_duration(Duration::from_millis(12_340)), "12.3 s");
+        assert_eq!(describe_duration(Duration::from_secs(125)), "2 min 5 s");
+    }
+
+    #[test]
+    fn old_marks_are_forgotten() {
+        let mut history = History::default();
+        let now = Instant::now();
+        for (row, mark) in [(0, Mark::Prompt), (3, finish("a", 0)), (9, finish("b", 2))] {
+            history.record(mark, row, now);
+        }
+        history.forget_before(4);
+        assert_eq!(history.commands().len(), 1);
+        assert_eq!(history.prompt_before(9), None);
+
+        // After a reset rows start over
+        history.record(Mark::Prompt, 0, now);
+        assert!(history.commands().is_empty());
+        assert_eq!(history.prompt_after(-1), Some(0));
+    }
+
+    #[test]
+    fn shells_load_their_script() {
+        let dir = Path::new("/run/rt");
+        let none = |_: &str| None;
+
+        let mut argv = vec!["-bash".to_string()];
+        let env = inject_into(dir, "/bin/bash", &mut argv, none);
+        assert_eq!(argv, ["bash", "--init-file", "/run/rt/rustyterm.bash"]);
+        assert_eq!(env, ["RUSTYTERM_BASH_LOGIN=1"]);
+
+        let mut argv = vec!["zsh".to_string()];
+        let env = inject_into(dir, "zsh", &mut argv, |_| Some("/home/me/.zsh".to_string()));
+        assert_eq!(env[0], "RUSTYTERM_ZDOTDIR=/home/me/.zsh");
+        assert_eq!(env[1], "ZDOTDIR=/run/rt/zsh");
+
+        let mut argv = vec!["fish".to_string()];
+        let env = inject_into(dir, "/usr/bin/fish", &mut argv, none);
+        assert_eq!(env, ["XDG_DATA_DIRS=/run/rt"]);
+    }
+}
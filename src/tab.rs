@@ -1,31 +1,108 @@
 use gtk4::prelude::*;
-use gtk4::{Box, Button, Label, Orientation, PopoverMenu, GestureClick};
-use gtk4::gio::Menu;
-use gtk4::gdk::Rectangle;
+use gtk4::{Box, Button, EventControllerFocus, Label, Orientation, Overlay, Paned, Widget};
+use gtk4::graphene::Rect;
 use vte4::TerminalExt;
 use std::cell::RefCell;
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
 
-use crate::config::Config;
-use crate::terminal::TerminalWidget;
+use crate::config::{Config, Profile};
+use crate::pane::{Direction, Pane};
+use crate::search::SearchBar;
+use crate::session::{Layout, SplitDirection};
+use crate::terminal::SpawnOptions;
+
+/// Pixels the divider moves per keyboard resize step.
+const RESIZE_STEP: i32 = 20;
+
+/// Source of tab ids used by remote control.
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+/// How to open a tab. Unset fields come from the profile.
+#[derive(Debug, Clone, Default)]
+pub struct TabOptions {
+    /// Profile name; the default profile when unset
+    pub profile: Option<String>,
+    /// Program and arguments to run instead of the profile's command
+    pub command: Option<Vec<String>>,
+    pub working_directory: Option<PathBuf>,
+    /// Directory of the program that asked for the tab, inherited like the
+    /// focused terminal's directory
+    pub parent_directory: Option<PathBuf>,
+    /// Fixed title, ignoring titles set by the terminal
+    pub title: Option<String>,
+    /// Keep the pane open after the command exits
+    pub hold: bool,
+}
 
 pub struct Tab {
-    pub container: Box,
-    pub terminal: TerminalWidget,
+    pub id: u32,
+    /// Notebook page, with the search bar floating over the split tree
+    pub container: Overlay,
+    /// Holds the split tree, either a pane or a Paned
+    tree: Box,
+    search: Rc<SearchBar>,
     pub label_box: Box,
     pub title_label: Label,
-    popover: Option<PopoverMenu>,
+    panes: Vec<Rc<Pane>>,
+    active: RefCell<Rc<Pane>>,
+    custom_title: RefCell<Option<String>>,
+    close_callback: RefCell<Option<Rc<dyn Fn()>>>,
 }
 
 impl Tab {
-    pub fn new(config: &Config) -> Rc<RefCell<Self>> {
-        let terminal = TerminalWidget::new(config);
+    pub fn new(profile: &Profile, options: &SpawnOptions, config: &Config) -> Rc<RefCell<Self>> {
+        let pane = Pane::new(profile, options, config);
+        let root = pane.container.clone().upcast();
+        Self::with_panes(&root, vec![pane])
+    }
+
+    /// Recreate a saved split tree. Each pane starts in its saved directory
+    /// when it still exists.
+    pub fn from_layout(layout: &Layout, config: &Config) -> Rc<RefCell<Self>> {
+        let mut panes = Vec::new();
+        let root = Self::build_layout(layout, config, &mut panes);
+        Self::with_panes(&root, panes)
+    }
 
-        // Container for the terminal
-        let container = Box::new(Orientation::Vertical, 0);
-        container.append(terminal.widget());
-        terminal.widget().set_hexpand(true);
-        terminal.widget().set_vexpand(true);
+    fn build_layout(layout: &Layout, config: &Config, panes: &mut Vec<Rc<Pane>>) -> Widget {
+        match layout {
+            Layout::Pane { profile, working_directory } => {
+                let profile = config.profile(profile);
+                let mut options = SpawnOptions::from_profile(&profile);
+                if let Some(dir) = working_directory.as_ref().filter(|dir| dir.is_dir()) {
+                    options.working_directory = Some(dir.clone());
+                }
+                options.inherit_directory(None, config);
+                let pane = Pane::new(&profile, &options, config);
+                panes.push(pane.clone());
+                pane.container.clone().upcast()
+            }
+            Layout::Split { direction, position, first, second } => {
+                let orientation = match direction {
+                    SplitDirection::Right => Orientation::Horizontal,
+                    SplitDirection::Down => Orientation::Vertical,
+                };
+                let paned = new_paned(orientation);
+                paned.set_start_child(Some(&Self::build_layout(first, config, panes)));
+                paned.set_end_child(Some(&Self::build_layout(second, config, panes)));
+                paned.set_position(*position);
+                paned.upcast()
+            }
+        }
+    }
+
+    /// Wrap a split tree holding `panes` in a tab. The first pane is focused.
+    fn with_panes(root: &Widget, panes: Vec<Rc<Pane>>) -> Rc<RefCell<Self>> {
+        // Container for the split tree, holding either a pane or a Paned
+        let tree = Box::new(Orientation::Vertical, 0);
+        tree.append(root);
+
+        let search = SearchBar::new();
+        let container = Overlay::new();
+        container.set_child(Some(&tree));
+        container.add_overlay(&search.revealer);
 
         // Tab label with close button
         let label_box = Box::new(Orientation::Horizontal, 4);
@@ -39,75 +116,337 @@ impl Tab {
         label_box.append(&close_button);
 
         let tab = Rc::new(RefCell::new(Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
             container,
-            terminal,
+            tree,
+            search,
             label_box,
             title_label,
-            popover: None,
+            active: RefCell::new(panes[0].clone()),
+            panes: panes.clone(),
+            custom_title: RefCell::new(None),
+            close_callback: RefCell::new(None),
         }));
 
-        // Setup right-click context menu
-        Self::setup_context_menu(&tab);
-
-        // Update title when window title changes
-        let tab_weak = Rc::downgrade(&tab);
-        tab.borrow().terminal.widget().connect_window_title_notify(move |term| {
-            if let Some(tab) = tab_weak.upgrade() {
-                let title = term.window_title()
-                    .map(|t| t.to_string())
-                    .unwrap_or_else(|| "Terminal".to_string());
-                let short_title = if title.len() > 20 {
-                    format!("{}...", &title[..17])
-                } else {
-                    title
-                };
-                tab.borrow().title_label.set_text(&short_title);
+        for pane in &panes {
+            Self::attach_pane(&tab, pane);
+        }
+
+        tab
+    }
+
+    /// The split tree with each pane's profile and directory, for saving.
+    pub fn layout(&self) -> Option<Layout> {
+        self.layout_of(&self.tree.first_child()?)
+    }
+
+    fn layout_of(&self, widget: &Widget) -> Option<Layout> {
+        if let Some(paned) = widget.downcast_ref::<Paned>() {
+            let direction = match paned.orientation() {
+                Orientation::Horizontal => SplitDirection::Right,
+                _ => SplitDirection::Down,
+            };
+            return Some(Layout::split(
+                direction,
+                paned.position(),
+                self.layout_of(&paned.start_child()?)?,
+                self.layout_of(&paned.end_child()?)?,
+            ));
+        }
+
+        let pane = self.panes.iter().find(|p| p.container.upcast_ref::<Widget>() == widget)?;
+        Some(Layout::Pane {
+            profile: pane.terminal.profile_name().to_string(),
+            working_directory: pane.terminal.current_directory(),
+        })
+    }
+
+    /// The fixed title, if any.
+    pub fn custom_title(&self) -> Option<String> {
+        self.custom_title.borrow().clone()
+    }
+
+    /// Track focus and title changes of a pane that belongs to this tab.
+    fn attach_pane(tab: &Rc<RefCell<Self>>, pane: &Rc<Pane>) {
+        // The last focused pane is the target of copy/paste and split actions
+        let focus = EventControllerFocus::new();
+        let tab_weak = Rc::downgrade(tab);
+        let pane_weak = Rc::downgrade(pane);
+        focus.connect_enter(move |_| {
+            if let (Some(tab), Some(pane)) = (tab_weak.upgrade(), pane_weak.upgrade()) {
+                let tab = tab.borrow();
+                if tab.search.is_open() {
+                    tab.search.set_terminal(pane.terminal.widget());
+                }
+                tab.active.replace(pane);
+                tab.update_title();
             }
         });
+        pane.terminal.widget().add_controller(focus);
 
-        tab
+        // Close the pane, or the whole tab, when its process exits
+        let tab_weak = Rc::downgrade(tab);
+        let pane_weak = Rc::downgrade(pane);
+        pane.set_exit_callback(move || {
+            let (Some(tab), Some(pane)) = (tab_weak.upgrade(), pane_weak.upgrade()) else {
+                return;
+            };
+            if tab.borrow().panes.len() > 1 {
+                Self::close_pane(&tab, &pane);
+            } else {
+                let callback = tab.borrow().close_callback.borrow().clone();
+                if let Some(callback) = callback {
+                    callback();
+                }
+            }
+        });
+
+        // Update title when window title of the focused pane changes
+        let tab_weak = Rc::downgrade(tab);
+        let pane_weak = Rc::downgrade(pane);
+        pane.terminal.widget().connect_window_title_notify(move |_| {
+            if let (Some(tab), Some(pane)) = (tab_weak.upgrade(), pane_weak.upgrade()) {
+                let tab = tab.borrow();
+                if Rc::ptr_eq(&tab.active_pane(), &pane) {
+                    tab.update_title();
+                }
+            }
+        });
     }
 
+    fn update_title(&self) {
+        let title = self.custom_title.borrow().clone()
+            .or_else(|| self.active_pane().terminal.widget().window_title().map(|t| t.to_string()))
+            .unwrap_or_else(|| "Terminal".to_string());
+        self.title_label.set_text(&short_title(&title));
+    }
+
+    /// Use a fixed title instead of the one set by the terminal, or go back
+    /// to the terminal's title with `None`.
+    pub fn set_custom_title(&self, title: Option<String>) {
+        self.custom_title.replace(title);
+        self.update_title();
+    }
+
+    /// The pane that last had keyboard focus.
+    pub fn active_pane(&self) -> Rc<Pane> {
+        self.active.borrow().clone()
+    }
+
+    /// Open the search bar on the focused pane.
+    pub fn show_search(&self) {
+        self.search.open(self.active_pane().terminal.widget());
+    }
+
+    pub fn panes(&self) -> &[Rc<Pane>] {
+        &self.panes
+    }
+
+    /// Split the focused pane, placing a new terminal to the right
+    /// (`Orientation::Horizontal`) or below (`Orientation::Vertical`).
+    /// The new terminal uses the same profile as the focused one.
+    pub fn split(tab: &Rc<RefCell<Self>>, orientation: Orientation, config: &Config) {
+        let active = tab.borrow().active_pane();
+        let old: Widget = active.container.clone().upcast();
+        let size = match orientation {
+            Orientation::Horizontal => old.width(),
+            _ => old.height(),
+        };
+
+        let paned = new_paned(orientation);
+
+        tab.borrow().replace_child(&old, paned.upcast_ref());
+
+        let profile = config.profile(active.terminal.profile_name());
+        let mut options = SpawnOptions::from_profile(&profile);
+        options.inherit_directory(active.terminal.current_directory(), config);
+        let pane = Pane::new(&profile, &options, config);
+        paned.set_start_child(Some(&old));
+        paned.set_end_child(Some(&pane.container));
+        if size > 0 {
+            paned.set_position(size / 2);
+        }
+
+        tab.borrow_mut().panes.push(pane.clone());
+        Self::attach_pane(tab, &pane);
+
+        pane.terminal.widget().grab_focus();
+    }
+
+    /// Remove a pane from the split tree, letting its sibling take its space.
+    /// The last pane of a tab cannot be closed this way; close the tab instead.
+    pub fn close_pane(tab: &Rc<RefCell<Self>>, pane: &Rc<Pane>) {
+        let widget: Widget = pane.container.clone().upcast();
+        let Some(paned) = widget.parent().and_then(|p| p.downcast::<Paned>().ok()) else {
+            return;
+        };
+        let sibling = if paned.start_child().as_ref() == Some(&widget) {
+            paned.end_child()
+        } else {
+            paned.start_child()
+        };
+        let Some(sibling) = sibling else {
+            return;
+        };
+
+        paned.set_start_child(None::<&Widget>);
+        paned.set_end_child(None::<&Widget>);
+        tab.borrow().replace_child(paned.upcast_ref(), &sibling);
+        pane.cleanup();
+
+        tab.borrow_mut().panes.retain(|p| !Rc::ptr_eq(p, pane));
+
+        // Hand focus to a pane that took over the freed space
+        let next = {
+            let tab = tab.borrow();
+            tab.panes
+                .iter()
+                .find(|p| p.container.upcast_ref::<Widget>() == &sibling
+                    || p.container.is_ancestor(&sibling))
+                .cloned()
+        };
+        if let Some(next) = next {
+            tab.borrow().active.replace(next.clone());
+            tab.borrow().update_title();
+            next.terminal.widget().grab_focus();
+        }
+    }
+
+    /// Put `new` where `old` currently sits in the split tree.
+    fn replace_child(&self, old: &Widget, new: &Widget) {
+        match old.parent().and_then(|p| p.downcast::<Paned>().ok()) {
+            Some(paned) => {
+                if paned.start_child().as_ref() == Some(old) {
+                    paned.set_start_child(Some(new));
+                } else {
+                    paned.set_end_child(Some(new));
+                }
+            }
+            None => {
+                self.tree.remove(old);
+                self.tree.append(new);
+            }
+        }
+    }
+
+    /// Move keyboard focus to the nearest pane in the given direction.
+    pub fn focus_direction(&self, direction: Direction) {
+        let active = self.active_pane();
+        let Some(from) = active.container.compute_bounds(&self.container) else {
+            return;
+        };
+
+        let target = self.panes
+            .iter()
+            .filter(|p| !Rc::ptr_eq(p, &active))
+            .filter_map(|p| {
+                let bounds = p.container.compute_bounds(&self.container)?;
+                directional_distance(&from, &bounds, direction).map(|d| (p, d))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(p, _)| p.clone());
+
+        if let Some(pane) = target {
+            pane.terminal.widget().grab_focus();
+        }
+    }
+
+    /// Move the divider of the closest enclosing split along `direction`.
+    pub fn resize_active(&self, direction: Direction) {
+        let orientation = direction.orientation();
+        let mut widget = self.active_pane().container.parent();
+
+        while let Some(current) = widget {
+            if let Some(paned) = current.downcast_ref::<Paned>()
+                && paned.orientation() == orientation
+            {
+                let delta = match direction {
+                    Direction::Left | Direction::Up => -RESIZE_STEP,
+                    Direction::Right | Direction::Down => RESIZE_STEP,
+                };
+                paned.set_position((paned.position() + delta).max(0));
+                return;
+            }
+            if current == *self.tree.upcast_ref::<Widget>() {
+                return;
+            }
+            widget = current.parent();
+        }
+    }
+
+    /// Called to close the tab, from its close button or when the process
+    /// of its last pane exits.
     pub fn set_close_callback<F>(&self, callback: F)
     where
         F: Fn() + 'static,
     {
+        let callback: Rc<dyn Fn()> = Rc::new(callback);
         if let Some(close_btn) = self.label_box.last_child() {
             if let Some(button) = close_btn.downcast_ref::<Button>() {
+                let callback = callback.clone();
                 button.connect_clicked(move |_| callback());
             }
         }
+        self.close_callback.replace(Some(callback));
     }
 
-    fn setup_context_menu(tab: &Rc<RefCell<Self>>) {
-        let menu = Menu::new();
-        menu.append(Some("Copy"), Some("win.copy"));
-        menu.append(Some("Paste"), Some("win.paste"));
+    /// Cleanup resources before destroying the tab
+    pub fn cleanup(&mut self) {
+        for pane in &self.panes {
+            pane.cleanup();
+        }
+    }
+}
 
-        let popover = PopoverMenu::from_model(Some(&menu));
-        popover.set_parent(tab.borrow().terminal.widget());
-        popover.set_has_arrow(false);
+fn new_paned(orientation: Orientation) -> Paned {
+    let paned = Paned::new(orientation);
+    paned.set_hexpand(true);
+    paned.set_vexpand(true);
+    paned.set_shrink_start_child(false);
+    paned.set_shrink_end_child(false);
+    paned
+}
 
-        let gesture = GestureClick::new();
-        gesture.set_button(3); // Right mouse button
+/// Distance from `from` to a pane lying in `direction`, or `None` if it
+/// does not lie that way. Panes that line up with `from` are preferred.
+fn directional_distance(from: &Rect, to: &Rect, direction: Direction) -> Option<f32> {
+    let gap = match direction {
+        Direction::Left => from.x() - (to.x() + to.width()),
+        Direction::Right => to.x() - (from.x() + from.width()),
+        Direction::Up => from.y() - (to.y() + to.height()),
+        Direction::Down => to.y() - (from.y() + from.height()),
+    };
+    if gap < -1.0 {
+        return None;
+    }
 
-        let popover_clone = popover.clone();
-        gesture.connect_pressed(move |gesture, _, x, y| {
-            gesture.set_state(gtk4::EventSequenceState::Claimed);
-            popover_clone.set_pointing_to(Some(&Rectangle::new(x as i32, y as i32, 1, 1)));
-            popover_clone.popup();
-        });
+    let (from_center, to_center) = (from.center(), to.center());
+    let offset = match direction {
+        Direction::Left | Direction::Right => (from_center.y() - to_center.y()).abs(),
+        Direction::Up | Direction::Down => (from_center.x() - to_center.x()).abs(),
+    };
 
-        tab.borrow().terminal.widget().add_controller(gesture);
+    Some(gap.max(0.0) + offset)
+}
 
-        // Store popover reference for cleanup
-        tab.borrow_mut().popover = Some(popover);
+/// Title shortened to fit a tab label, cut on a character boundary.
+fn short_title(title: &str) -> String {
+    if title.chars().count() > 20 {
+        format!("{}...", title.chars().take(17).collect::<String>())
+    } else {
+        title.to_string()
     }
+}
 
-    /// Cleanup resources before destroying the tab
-    pub fn cleanup(&mut self) {
-        if let Some(popover) = self.popover.take() {
-            popover.unparent();
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_titles_are_cut_by_characters() {
+        assert_eq!(short_title("vim"), "vim");
+        assert_eq!(short_title("exactly twenty chars"), "exactly twenty chars");
+        assert_eq!(short_title("~/projects/rustyterm/src"), "~/projects/rustyt...");
+        assert_eq!(short_title("ééééééééééééééééééééé"), "ééééééééééééééééé...");
     }
 }
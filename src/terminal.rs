@@ -1,20 +1,187 @@
 use gtk4::prelude::*;
+use gtk4::{gio, glib::Unichar};
 use vte4::{Terminal, TerminalExt, TerminalExtManual};
 
-use crate::config::Config;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Instant;
+
+use crate::config::{Config, Matcher, Profile};
+use crate::matchers;
+use crate::remote;
+use crate::search::MATCH_MARKER;
+use crate::shell_integration::{self, Command, History};
 use crate::theme::{get_theme_by_name, Theme};
 
+/// Terminal type advertised to child processes.
+const TERM: &str = "xterm-256color";
+
+/// Limits of the font zoom.
+const MIN_FONT_SCALE: f64 = 0.25;
+const MAX_FONT_SCALE: f64 = 4.0;
+
+/// Distance between the tab stops VTE sets by default.
+const TAB_WIDTH: usize = 8;
+
+/// What to run in a terminal and the environment to run it in.
+#[derive(Debug, Clone, Default)]
+pub struct SpawnOptions {
+    /// Program followed by its arguments; empty runs `$SHELL`
+    pub command: Vec<String>,
+    /// Directory to start in; `None` uses the current directory
+    pub working_directory: Option<PathBuf>,
+    /// Variables set on top of the inherited environment
+    pub env: BTreeMap<String, String>,
+    /// Inherited variables removed from the environment
+    pub unset_env: Vec<String>,
+    /// Start as a login shell by prefixing argv[0] with '-'
+    pub login_shell: bool,
+}
+
+impl SpawnOptions {
+    /// Spawn options for the command, directory and environment of a profile.
+    pub fn from_profile(profile: &Profile) -> Self {
+        let command = profile.command.iter()
+            .chain(&profile.args)
+            .cloned()
+            .collect();
+        Self {
+            command,
+            working_directory: profile.working_directory.as_deref().map(expand_home),
+            env: profile.env.clone(),
+            unset_env: profile.unset_env.clone(),
+            login_shell: profile.login_shell,
+        }
+    }
+
+    /// Start in `inherited`, usually the focused terminal's directory, when
+    /// no directory is set and inheriting is enabled, falling back to `$HOME`.
+    pub fn inherit_directory(&mut self, inherited: Option<PathBuf>, config: &Config) {
+        if self.working_directory.is_some() {
+            return;
+        }
+        self.working_directory = inherited
+            .filter(|_| config.inherit_working_directory)
+            .filter(|dir| dir.is_dir())
+            .or_else(dirs::home_dir);
+    }
+
+    /// The program to execute and the argv it receives, argv[0] included.
+    fn argv(&self) -> (String, Vec<String>) {
+        let mut argv = if self.command.is_empty() {
+            vec![std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string())]
+        } else {
+            self.command.clone()
+        };
+        let program = argv[0].clone();
+
+        if self.login_shell {
+            let name = Path::new(&program)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| program.clone());
+            argv[0] = format!("-{}", name);
+        }
+        (program, argv)
+    }
+
+    /// Environment entries for VTE, which merges them into the inherited
+    /// environment: `NAME=value` sets a variable and a bare `NAME` unsets it.
+    fn envv(&self) -> Vec<String> {
+        let mut env = BTreeMap::new();
+        env.insert("TERM".to_string(), TERM.to_string());
+        env.insert("COLORTERM".to_string(), "truecolor".to_string());
+        env.insert("RUSTYTERM_VERSION".to_string(), env!("CARGO_PKG_VERSION").to_string());
+        env.extend(self.env.clone());
+
+        let mut unset_env = self.unset_env.clone();
+        match remote::socket_path() {
+            Some(path) => {
+                env.insert(remote::LISTEN_ON_VAR.to_string(), path.to_string_lossy().into_owned());
+            }
+            // Do not leak the socket of a rustyterm we were started from
+            None => unset_env.push(remote::LISTEN_ON_VAR.to_string()),
+        }
+        for name in &unset_env {
+            env.remove(name);
+        }
+
+        env.into_iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .chain(unset_env)
+            .collect()
+    }
+}
+
+/// Called with each command the shell integration reports finished.
+type CommandCallback = Rc<dyn Fn(&Command)>;
+
 pub struct TerminalWidget {
     terminal: Terminal,
+    profile_name: String,
+    options: SpawnOptions,
+    /// Process started by [`TerminalWidget::spawn`], while it runs
+    child_pid: Rc<Cell<Option<glib::Pid>>>,
+    /// Tag VTE gave each matcher's regex
+    matchers: RefCell<Vec<(i32, Matcher)>>,
+    /// Load the integration script into the shell at the next spawn
+    shell_integration: Cell<bool>,
+    /// Prompts and commands marked by the shell integration
+    history: Rc<RefCell<History>>,
+    command_callback: Rc<RefCell<Option<CommandCallback>>>,
 }
 
 impl TerminalWidget {
-    pub fn new(config: &Config) -> Self {
+    /// Create a terminal with the profile's appearance that runs `options`
+    /// once [`TerminalWidget::spawn`] is called. The profile is expected to
+    /// come from [`Config::profile`], with appearance filled in.
+    pub fn new(profile: &Profile, options: &SpawnOptions) -> Self {
         let terminal = Terminal::new();
+        // OSC 8 links; they open on Ctrl+click, see `crate::hyperlink`
+        terminal.set_allow_hyperlink(true);
+
+        let widget = Self {
+            terminal,
+            profile_name: profile.name.clone(),
+            options: options.clone(),
+            child_pid: Rc::new(Cell::new(None)),
+            matchers: RefCell::new(Vec::new()),
+            shell_integration: Cell::new(false),
+            history: Rc::new(RefCell::new(History::default())),
+            command_callback: Rc::new(RefCell::new(None)),
+        };
+        widget.apply_profile(profile);
+
+        let child_pid = widget.child_pid.clone();
+        widget.terminal.connect_child_exited(move |_, _| {
+            child_pid.set(None);
+        });
 
-        let widget = Self { terminal };
-        widget.apply_config(config);
-        widget.spawn_shell();
+        let history = widget.history.clone();
+        let command_callback = widget.command_callback.clone();
+        widget.terminal.connect_current_file_uri_notify(move |terminal| {
+            let Some(mark) = terminal.current_file_uri()
+                .and_then(|uri| shell_integration::parse_mark(&uri))
+            else {
+                return;
+            };
+            let (_, row) = terminal.cursor_position();
+            let finished = {
+                let mut history = history.borrow_mut();
+                history.forget_before(first_row(terminal));
+                history.record(mark, row, Instant::now()).cloned()
+            };
+            let callback = command_callback.borrow().clone();
+            if let (Some(command), Some(callback)) = (finished, callback) {
+                callback(&command);
+            }
+        });
 
         widget
     }
@@ -23,18 +190,141 @@ impl TerminalWidget {
         &self.terminal
     }
 
+    /// Name of the profile this terminal was opened with.
+    pub fn profile_name(&self) -> &str {
+        &self.profile_name
+    }
+
+    /// Re-apply appearance settings after the config changed.
     pub fn apply_config(&self, config: &Config) {
+        self.apply_profile(&config.profile(&self.profile_name));
+        self.set_matchers(&config.matchers);
+        self.set_shell_integration(config.shell_integration);
+    }
+
+    /// Load the integration script into shells started from now on.
+    pub fn set_shell_integration(&self, enabled: bool) {
+        self.shell_integration.set(enabled);
+    }
+
+    /// Called with each command the shell integration reports finished.
+    pub fn set_command_callback<F>(&self, callback: F)
+    where
+        F: Fn(&Command) + 'static,
+    {
+        self.command_callback.replace(Some(Rc::new(callback)));
+    }
+
+    /// Commands reported by the shell integration that are still in the
+    /// scrollback, oldest first.
+    pub fn commands(&self) -> Vec<Command> {
+        let mut history = self.history.borrow_mut();
+        history.forget_before(first_row(&self.terminal));
+        history.commands().to_vec()
+    }
+
+    /// Scroll the prompt above the top of the view, or below it when
+    /// `forward`, to the top. Returns whether there was one.
+    pub fn scroll_to_prompt(&self, forward: bool) -> bool {
+        let Some(adjustment) = self.terminal.vadjustment() else {
+            return false;
+        };
+        let top = adjustment.value() as i64;
+        let history = self.history.borrow();
+        let prompt = if forward {
+            history.prompt_after(top)
+        } else {
+            history.prompt_before(top)
+        };
+        match prompt {
+            Some(row) => adjustment.set_value(row as f64),
+            // Past the last prompt is where new output goes
+            None if forward => adjustment.set_value(adjustment.upper()),
+            None => return false,
+        }
+        true
+    }
+
+    /// Scroll the prompt `command` was entered at to the top.
+    pub fn scroll_to_command(&self, command: &Command) {
+        if let Some(adjustment) = self.terminal.vadjustment() {
+            adjustment.set_value(command.prompt_row as f64);
+        }
+    }
+
+    /// Output of the last command reported by the shell integration.
+    pub fn last_output(&self) -> Option<String> {
+        let command = self.commands().pop()?;
+        self.rows_text(command.output_row, command.end_row).ok()
+    }
+
+    /// Text of the lines starting on rows `start` to `end`, excluded,
+    /// counted from the top of the buffer.
+    fn rows_text(&self, start: i64, end: i64) -> Result<String, glib::Error> {
+        let text = scrollback_text(&self.terminal)?;
+        let columns = self.terminal.column_count().max(1) as usize;
+        let mut row = first_row(&self.terminal);
+        let mut lines = Vec::new();
+        for line in text.lines() {
+            if row >= end {
+                break;
+            }
+            if row >= start {
+                lines.push(line);
+            }
+            row += rows_of(line, columns) as i64;
+        }
+        Ok(lines.join("\n").trim_end().to_string())
+    }
+
+    /// Make the text found by the `custom` matchers and the built-in ones
+    /// clickable. Matchers whose regex does not compile are skipped.
+    pub fn set_matchers(&self, custom: &[Matcher]) {
+        self.terminal.match_remove_all();
+        let mut added = Vec::new();
+        for matcher in custom.iter().cloned().chain(matchers::builtin()) {
+            if let Ok(regex) = matchers::compile(&matcher.regex) {
+                let tag = self.terminal.match_add_regex(&regex, 0);
+                self.terminal.match_set_cursor_name(tag, "pointer");
+                added.push((tag, matcher));
+            }
+        }
+        self.matchers.replace(added);
+    }
+
+    /// The matched text at `x`, `y` and the matcher that found it.
+    pub fn match_at(&self, x: f64, y: f64) -> Option<(String, Matcher)> {
+        let (text, tag) = self.terminal.check_match_at(x, y);
+        let text = text?;
+        self.matchers.borrow().iter()
+            .find(|(matcher_tag, _)| *matcher_tag == tag)
+            .map(|(_, matcher)| (text.to_string(), matcher.clone()))
+    }
+
+    /// Target of the OSC 8 hyperlink at `x`, `y`.
+    pub fn hyperlink_at(&self, x: f64, y: f64) -> Option<String> {
+        self.terminal.check_hyperlink_at(x, y).map(|uri| uri.to_string())
+    }
+
+    fn apply_profile(&self, profile: &Profile) {
         // Set font
-        let font_desc = format!("{} {}", config.font_family, config.font_size);
-        self.terminal.set_font_desc(Some(
-            &gtk4::pango::FontDescription::from_string(&font_desc),
-        ));
+        if let (Some(family), Some(size)) = (&profile.font_family, profile.font_size) {
+            let font_desc = format!("{} {}", family, size);
+            self.terminal.set_font_desc(Some(
+                &gtk4::pango::FontDescription::from_string(&font_desc),
+            ));
+        }
 
         // Set scrollback
-        self.terminal.set_scrollback_lines(config.scrollback_lines);
+        if let Some(lines) = profile.scrollback_lines {
+            self.terminal.set_scrollback_lines(lines);
+        }
 
         // Apply theme
-        self.apply_theme(&get_theme_by_name(&config.theme));
+        // Unknown names are reported when the config is loaded
+        if let Some(theme) = profile.theme.as_deref().and_then(get_theme_by_name) {
+            self.apply_theme(&theme);
+        }
     }
 
     pub fn apply_theme(&self, theme: &Theme) {
@@ -52,31 +342,109 @@ impl TerminalWidget {
         self.terminal.set_color_cursor_foreground(Some(&bg));
     }
 
-    fn spawn_shell(&self) {
-        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
+    /// Start the terminal's command, calling `on_error` if it cannot be
+    /// started. May be called again after the command exits.
+    pub fn spawn<F>(&self, on_error: F)
+    where
+        F: FnOnce(glib::Error) + 'static,
+    {
+        let (program, mut argv) = self.options.argv();
+        let mut envv = self.options.envv();
+        if self.shell_integration.get() {
+            shell_integration::inject(&program, &mut argv, &mut envv);
+        }
+        let working_directory = self.options.working_directory.as_ref()
+            .map(|dir| dir.to_string_lossy().into_owned());
 
         let pty_flags = vte4::PtyFlags::DEFAULT;
-        let spawn_flags = glib::SpawnFlags::DEFAULT;
+        // argv[0] may differ from the program, as for login shells
+        let spawn_flags = glib::SpawnFlags::SEARCH_PATH | glib::SpawnFlags::FILE_AND_ARGV_ZERO;
 
-        let args: &[&str] = &[&shell];
-        let envv: &[&str] = &[];
+        let args: Vec<&str> = std::iter::once(program.as_str())
+            .chain(argv.iter().map(String::as_str))
+            .collect();
+        let envv: Vec<&str> = envv.iter().map(String::as_str).collect();
+        let child_pid = self.child_pid.clone();
 
         self.terminal.spawn_async(
             pty_flags,
-            None,  // working directory (None = current)
-            args,
-            envv,
+            working_directory.as_deref(),  // None = current
+            &args,
+            &envv,
             spawn_flags,
             || {},
             -1,    // timeout (-1 = default)
-            gtk4::gio::Cancellable::NONE,
-            |_result| {},
+            gio::Cancellable::NONE,
+            move |result| match result {
+                Ok(pid) => child_pid.set(Some(pid)),
+                Err(e) => on_error(e),
+            },
         );
     }
 
-    pub fn get_current_directory(&self) -> Option<String> {
-        self.terminal.current_directory_uri()
-            .map(|uri| uri.to_string())
+    /// Name of the process in the terminal's foreground, unless it is the
+    /// terminal's own command (usually an idle shell) or nothing runs.
+    pub fn foreground_process(&self) -> Option<String> {
+        let child = self.child_pid.get()?;
+        let pty = self.terminal.pty()?;
+        // SAFETY: the descriptor stays open while `pty` is alive
+        let group = unsafe { libc::tcgetpgrp(pty.fd().as_raw_fd()) };
+        if group <= 0 || group == child.0 {
+            return None;
+        }
+
+        let name = fs::read_to_string(format!("/proc/{}/comm", group))
+            .map(|name| name.trim().to_string())
+            .unwrap_or_else(|_| format!("process {}", group));
+        Some(name)
+    }
+
+    /// Local directory reported by the shell through OSC 7, if any.
+    pub fn current_directory(&self) -> Option<PathBuf> {
+        let uri = self.terminal.current_directory_uri()?;
+        local_path_from_uri(&uri, &glib::host_name())
+    }
+
+    /// Type `text` into the terminal as if it came from the keyboard.
+    pub fn send_text(&self, text: &str) {
+        self.send_bytes(text.as_bytes());
+    }
+
+    /// Send raw bytes to the terminal's command.
+    pub fn send_bytes(&self, bytes: &[u8]) {
+        self.terminal.feed_child(bytes);
+    }
+
+    /// Scale the font by `factor`, relative to the current scale.
+    pub fn zoom(&self, factor: f64) {
+        let scale = (self.terminal.font_scale() * factor).clamp(MIN_FONT_SCALE, MAX_FONT_SCALE);
+        self.terminal.set_font_scale(scale);
+    }
+
+    pub fn reset_zoom(&self) {
+        self.terminal.set_font_scale(1.0);
+    }
+
+    /// Text on screen, or the whole scrollback when `scrollback` is set.
+    pub fn text(&self, scrollback: bool) -> Result<String, glib::Error> {
+        if scrollback {
+            return scrollback_text(&self.terminal);
+        }
+
+        // The view may be scrolled back, so read the rows it shows
+        let top = self.terminal.vadjustment().map_or(0, |adjustment| adjustment.value() as i64);
+        let rows = self.terminal.row_count().max(0);
+        let (text, _) = self.terminal.text_range_format(vte4::Format::Text, top, 0, top + rows, 0);
+        Ok(text.map(String::from).unwrap_or_default())
+    }
+
+    /// Scroll so that `row`, counted from the top of the scrollback, is in
+    /// the middle of the view.
+    pub fn scroll_to_row(&self, row: usize) {
+        if let Some(adjustment) = self.terminal.vadjustment() {
+            let value = adjustment.lower() + row as f64 - adjustment.page_size() / 2.0;
+            adjustment.set_value(value);
+        }
     }
 
     pub fn copy_clipboard(&self) {
@@ -91,3 +459,164 @@ impl TerminalWidget {
         self.terminal.has_selection()
     }
 }
+
+/// Text of the whole scrollback and screen of `terminal`.
+pub fn scrollback_text(terminal: &Terminal) -> Result<String, glib::Error> {
+    let stream = gio::MemoryOutputStream::new_resizable();
+    terminal.write_contents_sync(
+        &stream,
+        vte4::WriteFlags::Default,
+        gio::Cancellable::NONE,
+    )?;
+    stream.close(gio::Cancellable::NONE)?;
+    let bytes = stream.steal_as_bytes();
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Row of the oldest line kept in the scrollback, as counted by VTE's
+/// cursor position.
+fn first_row(terminal: &Terminal) -> i64 {
+    terminal.vadjustment().map_or(0, |adjustment| adjustment.lower() as i64)
+}
+
+/// Terminal rows taken by a line of `columns` wide terminal, which wraps
+/// long lines. Wide characters take two cells and move to the next row
+/// rather than being split, tabs move to the next tab stop without ever
+/// wrapping, and search markers do not count.
+pub fn rows_of(line: &str, columns: usize) -> usize {
+    let (mut rows, mut column) = (1, 0);
+    for c in line.chars().filter(|c| *c != MATCH_MARKER) {
+        if c == '\t' {
+            let stop = (column / TAB_WIDTH + 1) * TAB_WIDTH;
+            column = column.max(stop.min(columns.saturating_sub(1)));
+            continue;
+        }
+        let width = if c.is_zero_width() {
+            0
+        } else if c.is_wide() {
+            2
+        } else {
+            1
+        };
+        if column + width > columns {
+            rows += 1;
+            column = 0;
+        }
+        column += width;
+    }
+    rows
+}
+
+/// Expand a leading `~` to the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), dirs::home_dir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            home.join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Convert an OSC 7 `file://host/path` URI into a local path. URIs naming
+/// another host are rejected, as the directory does not exist here.
+fn local_path_from_uri(uri: &str, hostname: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    let (host, path) = rest.split_at(rest.find('/')?);
+    let is_local = host.is_empty()
+        || host.eq_ignore_ascii_case("localhost")
+        || host.eq_ignore_ascii_case(hostname);
+    if !is_local {
+        return None;
+    }
+    Some(PathBuf::from(OsString::from_vec(percent_decode(path)?)))
+}
+
+/// Decode `%XX` escapes, returning `None` for a malformed escape.
+pub fn percent_decode(text: &str) -> Option<Vec<u8>> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text.get(i + 1..i + 3)?;
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_lines_take_several_rows() {
+        assert_eq!(rows_of("", 80), 1);
+        assert_eq!(rows_of(&"x".repeat(80), 80), 1);
+        assert_eq!(rows_of(&"x".repeat(81), 80), 2);
+        assert_eq!(rows_of(&format!("{}ab", MATCH_MARKER), 2), 1);
+    }
+
+    #[test]
+    fn rows_count_display_width() {
+        // Two cells per CJK character or emoji, none for combining marks
+        assert_eq!(rows_of(&"漢".repeat(40), 80), 1);
+        assert_eq!(rows_of(&"漢".repeat(41), 80), 2);
+        assert_eq!(rows_of(&"🙂".repeat(41), 80), 2);
+        assert_eq!(rows_of(&"e\u{301}".repeat(80), 80), 1);
+        // A wide character does not fit in the last column
+        assert_eq!(rows_of(&format!("{}漢", "x".repeat(79)), 80), 2);
+        // Tabs expand to the next stop but stop at the last column
+        assert_eq!(rows_of(&"\tx".repeat(10), 80), 1);
+        assert_eq!(rows_of(&"\tx".repeat(11), 80), 2);
+        assert_eq!(rows_of(&format!("{}\t\t", "x".repeat(75)), 80), 1);
+    }
+
+    #[test]
+    fn local_uri_becomes_path() {
+        assert_eq!(
+            local_path_from_uri("file://myhost/home/user/src", "myhost"),
+            Some(PathBuf::from("/home/user/src"))
+        );
+        assert_eq!(
+            local_path_from_uri("file:///tmp", "myhost"),
+            Some(PathBuf::from("/tmp"))
+        );
+        assert_eq!(
+            local_path_from_uri("file://localhost/tmp", "myhost"),
+            Some(PathBuf::from("/tmp"))
+        );
+        assert_eq!(
+            local_path_from_uri("file://MyHost/tmp", "myhost"),
+            Some(PathBuf::from("/tmp"))
+        );
+    }
+
+    #[test]
+    fn escapes_are_decoded() {
+        assert_eq!(
+            local_path_from_uri("file://myhost/home/user/My%20Files/%C3%A9t%C3%A9", "myhost"),
+            Some(PathBuf::from("/home/user/My Files/été"))
+        );
+    }
+
+    #[test]
+    fn remote_host_is_rejected() {
+        assert_eq!(local_path_from_uri("file://server/home/user", "myhost"), None);
+    }
+
+    #[test]
+    fn invalid_uris_are_rejected() {
+        assert_eq!(local_path_from_uri("http://myhost/tmp", "myhost"), None);
+        assert_eq!(local_path_from_uri("file://myhost", "myhost"), None);
+        assert_eq!(local_path_from_uri("file:///tmp/%2", "myhost"), None);
+        assert_eq!(local_path_from_uri("file:///tmp/%zz", "myhost"), None);
+    }
+}
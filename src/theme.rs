@@ -1,8 +1,24 @@
 use gdk4::RGBA;
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+
+/// Palette sizes accepted by VTE.
+const PALETTE_SIZES: [usize; 4] = [8, 16, 232, 256];
+
+thread_local! {
+    /// Themes found by the last `load_themes` call
+    static THEMES: RefCell<Option<Vec<Theme>>> = const { RefCell::new(None) };
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Theme {
+    /// Defaults to the file name (without extension) for user themes.
+    #[serde(default)]
     pub name: String,
     pub background: String,
     pub foreground: String,
@@ -32,7 +48,134 @@ fn parse_color(hex: &str) -> RGBA {
     RGBA::parse(hex).unwrap_or_else(|_| RGBA::new(1.0, 1.0, 1.0, 1.0))
 }
 
+/// Format any color accepted by GDK as `#rrggbb`, the form other terminals expect.
+pub fn hex_color(color: &str) -> String {
+    let rgba = parse_color(color);
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(rgba.red()), channel(rgba.green()), channel(rgba.blue()))
+}
+
+/// Error raised while loading a user theme file.
+#[derive(Debug)]
+pub struct ThemeError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+impl std::error::Error for ThemeError {}
+
+/// Directory holding user-defined `*.toml` themes.
+pub fn themes_dir() -> PathBuf {
+    Config::config_dir().join("themes")
+}
+
+/// Parse and validate a single theme file.
+pub fn load_theme_file(path: &Path) -> Result<Theme, ThemeError> {
+    let error = |message: String| ThemeError {
+        path: path.to_path_buf(),
+        message,
+    };
+
+    let content = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+    let mut theme: Theme = toml::from_str(&content).map_err(|e| error(e.to_string()))?;
+
+    if theme.name.is_empty() {
+        theme.name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+    }
+
+    let colors = [
+        ("background", &theme.background),
+        ("foreground", &theme.foreground),
+        ("cursor", &theme.cursor),
+    ];
+    for (field, value) in colors {
+        if RGBA::parse(value.as_str()).is_err() {
+            return Err(error(format!("invalid {} color \"{}\"", field, value)));
+        }
+    }
+    for (i, value) in theme.palette.iter().enumerate() {
+        if RGBA::parse(value.as_str()).is_err() {
+            return Err(error(format!("invalid palette color {} \"{}\"", i, value)));
+        }
+    }
+    if !PALETTE_SIZES.contains(&theme.palette.len()) {
+        return Err(error(format!(
+            "palette has {} colors, expected 8, 16, 232 or 256",
+            theme.palette.len()
+        )));
+    }
+
+    Ok(theme)
+}
+
+/// Load every `*.toml` file in [`themes_dir`], sorted by file name.
+/// Files that fail to parse are returned as errors instead of being skipped silently.
+pub fn load_user_themes() -> (Vec<Theme>, Vec<ThemeError>) {
+    let mut themes = Vec::new();
+    let mut errors = Vec::new();
+
+    let dir = themes_dir();
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return (themes, errors),
+        Err(e) => {
+            errors.push(ThemeError { path: dir, message: e.to_string() });
+            return (themes, errors);
+        }
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        match load_theme_file(&path) {
+            Ok(theme) => themes.push(theme),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    (themes, errors)
+}
+
+/// Built-in themes merged with user themes. A user theme with the same
+/// name as a built-in one replaces it. The result is cached for
+/// `get_themes`, so call this again after the themes directory changes.
+pub fn load_themes() -> (Vec<Theme>, Vec<ThemeError>) {
+    let mut themes = builtin_themes();
+    let (user_themes, errors) = load_user_themes();
+
+    for theme in user_themes {
+        match themes.iter_mut().find(|t| t.name == theme.name) {
+            Some(existing) => *existing = theme,
+            None => themes.push(theme),
+        }
+    }
+
+    THEMES.with_borrow_mut(|cache| *cache = Some(themes.clone()));
+    (themes, errors)
+}
+
+/// Themes found by the last `load_themes` call, loading them on first use.
 pub fn get_themes() -> Vec<Theme> {
+    match THEMES.with_borrow(|cache| cache.clone()) {
+        Some(themes) => themes,
+        None => load_themes().0,
+    }
+}
+
+fn builtin_themes() -> Vec<Theme> {
     vec![
         Theme {
             name: "default".to_string(),
@@ -133,9 +276,6 @@ pub fn get_themes() -> Vec<Theme> {
     ]
 }
 
-pub fn get_theme_by_name(name: &str) -> Theme {
-    get_themes()
-        .into_iter()
-        .find(|t| t.name == name)
-        .unwrap_or_else(|| get_themes().into_iter().next().unwrap())
+pub fn get_theme_by_name(name: &str) -> Option<Theme> {
+    get_themes().into_iter().find(|t| t.name == name)
 }
@@ -1,35 +1,218 @@
 use gtk4::prelude::*;
 use gtk4::{
-    Application, ApplicationWindow, Box, Button, HeaderBar, MenuButton,
-    Notebook, Orientation,
+    Application, ApplicationWindow, Box, Button, ButtonsType, DialogFlags, FileChooserAction,
+    FileChooserDialog, HeaderBar, MenuButton, MessageDialog, MessageType, Notebook, Orientation,
+    ResponseType,
 };
 use gtk4::gio::{Menu, MenuItem, SimpleAction};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use vte4::TerminalExt;
 
-use crate::config::Config;
+use crate::banner::Banner;
+use crate::cli::Options;
+use crate::config::{Config, ExitAction};
+use crate::export::{export_theme, ExportFormat};
+use crate::global_search;
+use crate::import::{import_file, save_theme};
+use crate::pane::{Direction, Pane};
 use crate::resize::create_resize_overlay;
-use crate::tab::Tab;
-use crate::theme::{get_theme_by_name, get_themes};
+use crate::session::{TabState, WindowGeometry, WindowState};
+use crate::tab::{Tab, TabOptions};
+use crate::terminal::SpawnOptions;
+use crate::theme::{get_theme_by_name, load_themes, Theme};
+
+/// Font scale factor of one zoom step.
+const ZOOM_STEP: f64 = 1.1;
+
+/// Source of window ids used by remote control.
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
 
 pub struct RustyTermWindow {
+    pub id: u32,
     pub window: ApplicationWindow,
     notebook: Notebook,
+    pub banner: Banner,
+    theme_menu: Menu,
+    profile_menu: Menu,
     config: Rc<RefCell<Config>>,
     tabs: Rc<RefCell<Vec<Rc<RefCell<Tab>>>>>,
+    /// Ids of the tabs in the order they were last shown, most recent first
+    recent_tabs: Rc<RefCell<Vec<u32>>>,
+    /// Every open window, this one included
+    windows: Rc<RefCell<Vec<Rc<RustyTermWindow>>>>,
 }
 
 impl RustyTermWindow {
-    pub fn new(app: &Application) -> Self {
-        let config = Rc::new(RefCell::new(Config::load()));
+    /// Open a window whose first tab follows the command line `options`.
+    /// The window adds itself to `windows` and leaves it when destroyed.
+    pub fn new(
+        app: &Application,
+        config: &Rc<RefCell<Config>>,
+        windows: &Rc<RefCell<Vec<Rc<RustyTermWindow>>>>,
+        options: &Options,
+    ) -> Rc<Self> {
+        let win = Self::build(app, config, windows, options.tab.title.as_deref());
+        win.add_tab(&options.tab);
+        let remembered = win.remembered_geometry();
+        if let Some(remembered) = &remembered {
+            if remembered.width > 0 && remembered.height > 0 {
+                win.window.set_default_size(remembered.width, remembered.height);
+            }
+            win.window.set_maximized(remembered.maximized);
+            win.window.set_fullscreened(remembered.fullscreen);
+        }
+        let geometry = options.geometry.or_else(|| {
+            let config = config.borrow();
+            let grid = config.window_columns.zip(config.window_rows)?;
+            Some(remembered.and_then(|remembered| remembered.grid).unwrap_or(grid))
+        });
+        if let Some((cols, rows)) = geometry {
+            // Let the window take the natural size of a terminal this big,
+            // which VTE computes from the font's cell size
+            win.window.set_default_size(-1, -1);
+            if let Some(tab) = win.tabs.borrow().first() {
+                tab.borrow().active_pane().terminal.widget().set_size(cols, rows);
+            }
+        }
+        win
+    }
+
+    /// Reopen a window saved in a session.
+    pub fn restore(
+        app: &Application,
+        config: &Rc<RefCell<Config>>,
+        windows: &Rc<RefCell<Vec<Rc<RustyTermWindow>>>>,
+        state: &WindowState,
+    ) -> Rc<Self> {
+        let win = Self::build(app, config, windows, None);
+        if state.width > 0 && state.height > 0 {
+            win.window.set_default_size(state.width, state.height);
+        }
+        if state.maximized {
+            win.window.maximize();
+        } else {
+            win.window.unmaximize();
+        }
+        if state.fullscreen {
+            win.window.fullscreen();
+        } else {
+            win.window.unfullscreen();
+        }
+        for tab_state in &state.tabs {
+            let tab = Tab::from_layout(&tab_state.layout, &config.borrow());
+            if tab_state.title.is_some() {
+                tab.borrow().set_custom_title(tab_state.title.clone());
+            }
+            Self::insert_tab(&win.notebook, &win.config, &win.tabs, &win.window, &tab);
+        }
+        if state.tabs.is_empty() {
+            win.add_tab(&TabOptions::default());
+        }
+        win.focus_tab(state.current_tab);
+        win
+    }
+
+    /// The window's size and tabs, for saving in a session.
+    pub fn state(&self) -> WindowState {
+        let (width, height) = self.window.default_size();
+        let tabs = (0..self.notebook.n_pages())
+            .filter_map(|page| self.notebook.nth_page(Some(page)))
+            .filter_map(|page| {
+                let tab = self.tabs.borrow().iter()
+                    .find(|t| t.borrow().container.upcast_ref::<gtk4::Widget>() == &page)
+                    .cloned()?;
+                let tab = tab.borrow();
+                Some(TabState {
+                    title: tab.custom_title(),
+                    layout: tab.layout()?,
+                })
+            })
+            .collect();
+        WindowState {
+            width,
+            height,
+            maximized: self.window.is_maximized(),
+            fullscreen: self.window.is_fullscreen(),
+            tabs,
+            current_tab: self.notebook.current_page().unwrap_or(0) as usize,
+        }
+    }
+
+    /// Save the window's size and state as the one for new windows, when
+    /// the config asks for it.
+    pub fn remember_size(&self) {
+        let config = self.config.borrow();
+        if !config.remember_window_size {
+            return;
+        }
+
+        let (width, height) = self.window.default_size();
+        let sized_by_grid = config.window_columns.is_some() && config.window_rows.is_some();
+        let geometry = WindowGeometry {
+            width,
+            height,
+            maximized: self.window.is_maximized(),
+            fullscreen: self.window.is_fullscreen(),
+            grid: if sized_by_grid { self.grid_size() } else { None },
+        };
+        if let Err(e) = geometry.save() {
+            eprintln!("Failed to save the window size: {}", e);
+        }
+    }
+
+    /// Size and state saved by the last window closed, when the config
+    /// asks new windows to reuse them.
+    fn remembered_geometry(&self) -> Option<WindowGeometry> {
+        if !self.config.borrow().remember_window_size {
+            return None;
+        }
+        match WindowGeometry::load() {
+            Ok(geometry) => geometry,
+            Err(e) => {
+                self.banner.show_error(&format!("Failed to read the window size: {}", e));
+                None
+            }
+        }
+    }
+
+    /// Columns and rows of the terminal font that fit in the current tab.
+    fn grid_size(&self) -> Option<(i64, i64)> {
+        let tab = self.current()?;
+        let tab = tab.borrow();
+        let terminal = tab.active_pane().terminal.widget().clone();
+        let (cell_width, cell_height) = (terminal.char_width(), terminal.char_height());
+        if cell_width <= 0 || cell_height <= 0 {
+            return None;
+        }
+        Some((
+            i64::from(tab.container.width()) / cell_width,
+            i64::from(tab.container.height()) / cell_height,
+        ))
+    }
+
+    /// Create an empty window with its actions, ready for tabs.
+    fn build(
+        app: &Application,
+        config: &Rc<RefCell<Config>>,
+        windows: &Rc<RefCell<Vec<Rc<RustyTermWindow>>>>,
+        title: Option<&str>,
+    ) -> Rc<Self> {
+        let config = config.clone();
 
         let window = ApplicationWindow::builder()
             .application(app)
             .title("RustyTerm")
             .default_width(config.borrow().window_width)
             .default_height(config.borrow().window_height)
+            .maximized(config.borrow().window_maximized)
+            .fullscreened(config.borrow().window_fullscreen)
             .resizable(true)
             .build();
+        if let Some(title) = title {
+            window.set_title(Some(title));
+        }
 
         let notebook = Notebook::new();
         notebook.set_scrollable(true);
@@ -40,16 +223,31 @@ impl RustyTermWindow {
         let new_tab_btn = Button::from_icon_name("list-add-symbolic");
         new_tab_btn.set_tooltip_text(Some("New Tab (Ctrl+Shift+T)"));
         new_tab_btn.add_css_class("add-tab-button");
-        notebook.set_action_widget(&new_tab_btn, gtk4::PackType::End);
+
+        // Drop-down listing the profiles a new tab can be opened with
+        let profile_menu = Menu::new();
+        Self::fill_profile_menu(&profile_menu, &config.borrow());
+        let profile_btn = MenuButton::new();
+        profile_btn.set_tooltip_text(Some("New Tab with Profile"));
+        profile_btn.set_menu_model(Some(&profile_menu));
+        profile_btn.add_css_class("add-tab-button");
+
+        let new_tab_box = Box::new(Orientation::Horizontal, 0);
+        new_tab_box.append(&new_tab_btn);
+        new_tab_box.append(&profile_btn);
+        notebook.set_action_widget(&new_tab_box, gtk4::PackType::End);
 
         // Header bar with menu
+        let (themes, theme_errors) = load_themes();
         let header = HeaderBar::new();
-        let menu_button = Self::create_menu_button();
+        let (menu_button, theme_menu) = Self::create_menu_button(&themes);
         header.pack_end(&menu_button);
         window.set_titlebar(Some(&header));
 
         // Main container
         let main_box = Box::new(Orientation::Vertical, 0);
+        let banner = Banner::new();
+        main_box.append(&banner.revealer);
         main_box.append(&notebook);
 
         // Wrap content with resize overlay
@@ -59,39 +257,90 @@ impl RustyTermWindow {
         let tabs: Rc<RefCell<Vec<Rc<RefCell<Tab>>>>> = Rc::new(RefCell::new(Vec::new()));
 
         let win = Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
             window,
             notebook,
+            banner,
+            theme_menu,
+            profile_menu,
             config,
             tabs,
+            recent_tabs: Rc::new(RefCell::new(Vec::new())),
+            windows: windows.clone(),
         };
 
-        // Add first tab
-        win.add_tab();
+        if !theme_errors.is_empty() {
+            let messages: Vec<String> = theme_errors.iter().map(|e| e.to_string()).collect();
+            win.banner.show_error(&format!("Failed to load themes:\n{}", messages.join("\n")));
+        }
 
         // Setup actions and signals
         win.setup_actions(app);
         win.setup_new_tab_button(&new_tab_btn);
         win.setup_notebook_signals();
+        win.setup_close_confirmation();
+
+        let win = Rc::new(win);
 
+        // Runs only once the close has been confirmed
+        let win_weak = Rc::downgrade(&win);
+        win.window.connect_close_request(move |_| {
+            if let Some(win) = win_weak.upgrade() {
+                win.remember_size();
+            }
+            glib::Propagation::Proceed
+        });
+
+        let windows_clone = windows.clone();
+        win.window.connect_destroy(move |destroyed| {
+            windows_clone.borrow_mut().retain(|w| &w.window != destroyed);
+        });
+        windows.borrow_mut().push(win.clone());
         win
     }
 
-    fn create_menu_button() -> MenuButton {
+    fn create_menu_button(themes: &[Theme]) -> (MenuButton, Menu) {
         let menu = Menu::new();
 
+        let window_section = Menu::new();
+        window_section.append(Some("New Window"), Some("win.new-window"));
+        menu.append_section(None, &window_section);
+
+        let search_section = Menu::new();
+        search_section.append(Some("Search All Tabs…"), Some("win.search-all-tabs"));
+        menu.append_section(None, &search_section);
+
         // Theme submenu
         let theme_menu = Menu::new();
-        for theme in get_themes() {
+        for theme in themes {
             let item = MenuItem::new(Some(&theme.name), Some(&format!("win.set-theme::{}", theme.name)));
             theme_menu.append_item(&item);
         }
         menu.append_submenu(Some("Themes"), &theme_menu);
 
+        let import_section = Menu::new();
+        import_section.append(Some("Import Theme…"), Some("win.import-theme"));
+        import_section.append(Some("Export Theme…"), Some("win.export-theme"));
+        menu.append_section(None, &import_section);
+
+        let quit_section = Menu::new();
+        quit_section.append(Some("Quit"), Some("app.quit"));
+        menu.append_section(None, &quit_section);
+
         let menu_button = MenuButton::new();
         menu_button.set_icon_name("open-menu-symbolic");
         menu_button.set_menu_model(Some(&menu));
 
-        menu_button
+        (menu_button, theme_menu)
+    }
+
+    /// List every configured profile in the new tab drop-down.
+    fn fill_profile_menu(menu: &Menu, config: &Config) {
+        menu.remove_all();
+        for name in config.profile_names() {
+            let action = format!("win.new-tab-profile::{}", name);
+            menu.append_item(&MenuItem::new(Some(&name), Some(&action)));
+        }
     }
 
     fn setup_actions(&self, app: &Application) {
@@ -99,6 +348,22 @@ impl RustyTermWindow {
         let notebook = self.notebook.clone();
         let tabs = self.tabs.clone();
         let window = self.window.clone();
+        let windows = self.windows.clone();
+
+        // New window action, starting in the directory of the focused terminal
+        let new_window_action = SimpleAction::new("new-window", None);
+        let app_clone = app.clone();
+        let config_clone = config.clone();
+        let notebook_clone = notebook.clone();
+        let tabs_clone = tabs.clone();
+        let windows_clone = windows.clone();
+        new_window_action.connect_activate(move |_, _| {
+            let mut options = Options::default();
+            options.tab.parent_directory = Self::current_tab(&notebook_clone, &tabs_clone)
+                .and_then(|tab| tab.borrow().active_pane().terminal.current_directory());
+            Self::new(&app_clone, &config_clone, &windows_clone, &options).present();
+        });
+        window.add_action(&new_window_action);
 
         // New tab action
         let new_tab_action = SimpleAction::new("new-tab", None);
@@ -107,48 +372,151 @@ impl RustyTermWindow {
         let tabs_clone = tabs.clone();
         let window_clone = window.clone();
         new_tab_action.connect_activate(move |_, _| {
-            Self::create_new_tab(&notebook_clone, &config_clone, &tabs_clone, &window_clone);
+            Self::create_new_tab(&notebook_clone, &config_clone, &tabs_clone, &window_clone, &TabOptions::default());
         });
         window.add_action(&new_tab_action);
 
+        // New tab running a program, such as an editor opened by a matcher
+        let new_tab_command_action = SimpleAction::new("new-tab-command", Some(glib::VariantTy::STRING_ARRAY));
+        let config_clone = config.clone();
+        let notebook_clone = notebook.clone();
+        let tabs_clone = tabs.clone();
+        let window_clone = window.clone();
+        new_tab_command_action.connect_activate(move |_, param| {
+            if let Some(command) = param.and_then(|p| p.get::<Vec<String>>())
+                && !command.is_empty()
+            {
+                let options = TabOptions {
+                    command: Some(command),
+                    ..TabOptions::default()
+                };
+                Self::create_new_tab(&notebook_clone, &config_clone, &tabs_clone, &window_clone, &options);
+            }
+        });
+        window.add_action(&new_tab_command_action);
+
+        // New tab with a named profile
+        let new_tab_profile_action = SimpleAction::new("new-tab-profile", Some(glib::VariantTy::STRING));
+        let config_clone = config.clone();
+        let notebook_clone = notebook.clone();
+        let tabs_clone = tabs.clone();
+        let window_clone = window.clone();
+        new_tab_profile_action.connect_activate(move |_, param| {
+            if let Some(profile) = param.and_then(|p| p.str()) {
+                let options = TabOptions {
+                    profile: Some(profile.to_string()),
+                    ..TabOptions::default()
+                };
+                Self::create_new_tab(&notebook_clone, &config_clone, &tabs_clone, &window_clone, &options);
+            }
+        });
+        window.add_action(&new_tab_profile_action);
+
         // Close tab action
         let close_tab_action = SimpleAction::new("close-tab", None);
+        let config_clone = config.clone();
         let notebook_clone = notebook.clone();
         let tabs_clone = tabs.clone();
         let window_clone = window.clone();
         close_tab_action.connect_activate(move |_, _| {
             let page = notebook_clone.current_page();
             if let Some(idx) = page {
-                Self::close_tab_at(&notebook_clone, &tabs_clone, &window_clone, idx as usize);
+                Self::close_tab_at(&notebook_clone, &tabs_clone, &window_clone, &config_clone, idx as usize);
             }
         });
         window.add_action(&close_tab_action);
 
-        // Theme action
+        // Theme action, applied to every window
         let set_theme_action = SimpleAction::new("set-theme", Some(glib::VariantTy::STRING));
         let config_clone = config.clone();
-        let tabs_clone = tabs.clone();
+        let windows_clone = windows.clone();
+        let banner = self.banner.clone();
         set_theme_action.connect_activate(move |_, param| {
             if let Some(theme_name) = param.and_then(|p| p.str()) {
-                let theme = get_theme_by_name(theme_name);
-                for tab in tabs_clone.borrow().iter() {
-                    tab.borrow().terminal.apply_theme(&theme);
+                // Keybindings can name any theme, which must not reach the config
+                if get_theme_by_name(theme_name).is_none() {
+                    banner.show_error(&format!("Unknown theme \"{}\"", theme_name));
+                    return;
                 }
                 config_clone.borrow_mut().theme = theme_name.to_string();
-                let _ = config_clone.borrow().save();
+                for window in windows_clone.borrow().iter() {
+                    window.apply_config(&config_clone.borrow());
+                }
+                if let Err(e) = Config::save_value("theme", theme_name) {
+                    banner.show_error(&format!("Theme applied but not saved: {}", e));
+                }
             }
         });
         window.add_action(&set_theme_action);
 
+        // Import theme action
+        let import_theme_action = SimpleAction::new("import-theme", None);
+        let window_clone = window.clone();
+        let banner = self.banner.clone();
+        let windows_clone = windows.clone();
+        import_theme_action.connect_activate(move |_, _| {
+            let dialog = FileChooserDialog::new(
+                Some("Import Theme"),
+                Some(&window_clone),
+                FileChooserAction::Open,
+                &[("_Cancel", ResponseType::Cancel), ("_Import", ResponseType::Accept)],
+            );
+            dialog.set_modal(true);
+
+            let banner = banner.clone();
+            let windows = windows_clone.clone();
+            let set_theme_action = set_theme_action.clone();
+            dialog.connect_response(move |dialog, response| {
+                if response == ResponseType::Accept
+                    && let Some(path) = dialog.file().and_then(|f| f.path())
+                {
+                    Self::import_theme(&path, &banner, &windows, &set_theme_action);
+                }
+                dialog.close();
+            });
+            dialog.present();
+        });
+        window.add_action(&import_theme_action);
+
+        // Export theme action
+        let export_theme_action = SimpleAction::new("export-theme", None);
+        let window_clone = window.clone();
+        let banner = self.banner.clone();
+        let config_clone = config.clone();
+        export_theme_action.connect_activate(move |_, _| {
+            let dialog = FileChooserDialog::new(
+                Some("Export Theme"),
+                Some(&window_clone),
+                FileChooserAction::Save,
+                &[("_Cancel", ResponseType::Cancel), ("_Export", ResponseType::Accept)],
+            );
+            dialog.set_modal(true);
+            let theme_name = config_clone.borrow().theme.clone();
+            dialog.set_current_name(&format!("{}.toml", theme_name));
+
+            let banner = banner.clone();
+            dialog.connect_response(move |dialog, response| {
+                if response == ResponseType::Accept
+                    && let Some(path) = dialog.file().and_then(|f| f.path())
+                {
+                    match get_theme_by_name(&theme_name) {
+                        Some(theme) => Self::export_theme(&path, &theme, &banner),
+                        None => banner.show_error(&format!("Unknown theme \"{}\"", theme_name)),
+                    }
+                }
+                dialog.close();
+            });
+            dialog.present();
+        });
+        window.add_action(&export_theme_action);
+
         // Copy action
         let copy_action = SimpleAction::new("copy", None);
         let tabs_clone = tabs.clone();
         let notebook_clone = notebook.clone();
         copy_action.connect_activate(move |_, _| {
-            if let Some(idx) = notebook_clone.current_page() {
-                if let Some(tab) = tabs_clone.borrow().get(idx as usize) {
-                    tab.borrow().terminal.copy_clipboard();
-                }
+            if let Some(tab) = Self::current_tab(&notebook_clone, &tabs_clone) {
+                tab.borrow().active_pane().terminal.copy_clipboard();
             }
         });
         window.add_action(&copy_action);
@@ -158,19 +526,280 @@ impl RustyTermWindow {
         let tabs_clone = tabs.clone();
         let notebook_clone = notebook.clone();
         paste_action.connect_activate(move |_, _| {
-            if let Some(idx) = notebook_clone.current_page() {
-                if let Some(tab) = tabs_clone.borrow().get(idx as usize) {
-                    tab.borrow().terminal.paste_clipboard();
-                }
+            if let Some(tab) = Self::current_tab(&notebook_clone, &tabs_clone) {
+                tab.borrow().active_pane().terminal.paste_clipboard();
             }
         });
         window.add_action(&paste_action);
 
-        // Keyboard shortcuts
-        app.set_accels_for_action("win.new-tab", &["<Ctrl><Shift>t"]);
-        app.set_accels_for_action("win.close-tab", &["<Ctrl><Shift>w"]);
-        app.set_accels_for_action("win.copy", &["<Ctrl><Shift>c"]);
-        app.set_accels_for_action("win.paste", &["<Ctrl><Shift>v"]);
+        // Search the scrollback of the focused pane
+        let search_action = SimpleAction::new("search", None);
+        let tabs_clone = tabs.clone();
+        let notebook_clone = notebook.clone();
+        search_action.connect_activate(move |_, _| {
+            if let Some(tab) = Self::current_tab(&notebook_clone, &tabs_clone) {
+                tab.borrow().show_search();
+            }
+        });
+        window.add_action(&search_action);
+
+        // Search the scrollback of every terminal in every window
+        let search_all_action = SimpleAction::new("search-all-tabs", None);
+        let window_clone = window.clone();
+        let windows_clone = windows.clone();
+        search_all_action.connect_activate(move |_, _| {
+            global_search::show(&window_clone, &windows_clone);
+        });
+        window.add_action(&search_all_action);
+
+        // Prompts and output marked by the shell integration
+        for (name, forward) in [("previous-prompt", false), ("next-prompt", true)] {
+            let action = SimpleAction::new(name, None);
+            let tabs_clone = tabs.clone();
+            let notebook_clone = notebook.clone();
+            action.connect_activate(move |_, _| {
+                if let Some(tab) = Self::current_tab(&notebook_clone, &tabs_clone) {
+                    tab.borrow().active_pane().terminal.scroll_to_prompt(forward);
+                }
+            });
+            window.add_action(&action);
+        }
+
+        let copy_output_action = SimpleAction::new("copy-last-output", None);
+        let tabs_clone = tabs.clone();
+        let notebook_clone = notebook.clone();
+        let banner = self.banner.clone();
+        copy_output_action.connect_activate(move |_, _| {
+            let Some(tab) = Self::current_tab(&notebook_clone, &tabs_clone) else {
+                return;
+            };
+            let pane = tab.borrow().active_pane();
+            match pane.terminal.last_output() {
+                Some(output) => pane.terminal.widget().clipboard().set_text(&output),
+                None => banner.show_info(
+                    "No command output to copy. Commands are only recorded in bash, zsh and fish with shell_integration enabled.",
+                ),
+            }
+        });
+        window.add_action(&copy_output_action);
+
+        // Split actions
+        for (name, orientation) in [
+            ("split-right", Orientation::Horizontal),
+            ("split-down", Orientation::Vertical),
+        ] {
+            let split_action = SimpleAction::new(name, None);
+            let config_clone = config.clone();
+            let tabs_clone = tabs.clone();
+            let notebook_clone = notebook.clone();
+            split_action.connect_activate(move |_, _| {
+                if let Some(tab) = Self::current_tab(&notebook_clone, &tabs_clone) {
+                    Tab::split(&tab, orientation, &config_clone.borrow());
+                }
+            });
+            window.add_action(&split_action);
+        }
+
+        // Close pane action (closes the tab when it is the last pane)
+        let close_pane_action = SimpleAction::new("close-pane", None);
+        let config_clone = config.clone();
+        let notebook_clone = notebook.clone();
+        let tabs_clone = tabs.clone();
+        let window_clone = window.clone();
+        close_pane_action.connect_activate(move |_, _| {
+            let Some(tab) = Self::current_tab(&notebook_clone, &tabs_clone) else {
+                return;
+            };
+            if tab.borrow().panes().len() > 1 {
+                let pane = tab.borrow().active_pane();
+                let processes = Self::processes_in(std::slice::from_ref(&pane), &config_clone.borrow());
+                Self::confirm_close(&window_clone, "Close this pane?", &processes, move || {
+                    Tab::close_pane(&tab, &pane);
+                });
+            } else if let Some(idx) = notebook_clone.current_page() {
+                Self::close_tab_at(&notebook_clone, &tabs_clone, &window_clone, &config_clone, idx as usize);
+            }
+        });
+        window.add_action(&close_pane_action);
+
+        // Focus pane action
+        let focus_pane_action = SimpleAction::new("focus-pane", Some(glib::VariantTy::STRING));
+        let tabs_clone = tabs.clone();
+        let notebook_clone = notebook.clone();
+        focus_pane_action.connect_activate(move |_, param| {
+            let direction = param.and_then(|p| p.str()).and_then(Direction::from_name);
+            if let (Some(direction), Some(tab)) =
+                (direction, Self::current_tab(&notebook_clone, &tabs_clone))
+            {
+                tab.borrow().focus_direction(direction);
+            }
+        });
+        window.add_action(&focus_pane_action);
+
+        // Resize pane action
+        let resize_pane_action = SimpleAction::new("resize-pane", Some(glib::VariantTy::STRING));
+        let tabs_clone = tabs.clone();
+        let notebook_clone = notebook.clone();
+        resize_pane_action.connect_activate(move |_, param| {
+            let direction = param.and_then(|p| p.str()).and_then(Direction::from_name);
+            if let (Some(direction), Some(tab)) =
+                (direction, Self::current_tab(&notebook_clone, &tabs_clone))
+            {
+                tab.borrow().resize_active(direction);
+            }
+        });
+        window.add_action(&resize_pane_action);
+
+        // Next and previous tab, wrapping around
+        for (name, step) in [("next-tab", 1), ("previous-tab", -1)] {
+            let switch_action = SimpleAction::new(name, None);
+            let notebook_clone = notebook.clone();
+            switch_action.connect_activate(move |_, _| {
+                let pages = notebook_clone.n_pages() as i32;
+                if let Some(current) = notebook_clone.current_page() {
+                    let page = (current as i32 + step).rem_euclid(pages.max(1));
+                    notebook_clone.set_current_page(Some(page as u32));
+                }
+            });
+            window.add_action(&switch_action);
+        }
+
+        // Go to the tab with a 1-based number
+        let goto_tab_action = SimpleAction::new("goto-tab", Some(glib::VariantTy::INT32));
+        let notebook_clone = notebook.clone();
+        goto_tab_action.connect_activate(move |_, param| {
+            if let Some(number) = param.and_then(|p| p.get::<i32>())
+                && number > 0
+                && (number as u32) <= notebook_clone.n_pages()
+            {
+                notebook_clone.set_current_page(Some(number as u32 - 1));
+            }
+        });
+        window.add_action(&goto_tab_action);
+
+        // Go back to the tab that was shown before this one
+        let last_used_tab_action = SimpleAction::new("last-used-tab", None);
+        let notebook_clone = notebook.clone();
+        let tabs_clone = tabs.clone();
+        let recent_tabs = self.recent_tabs.clone();
+        last_used_tab_action.connect_activate(move |_, _| {
+            let Some(&previous) = recent_tabs.borrow().get(1) else {
+                return;
+            };
+            let idx = tabs_clone.borrow().iter().position(|t| t.borrow().id == previous);
+            if let Some(idx) = idx {
+                notebook_clone.set_current_page(Some(idx as u32));
+            }
+        });
+        window.add_action(&last_used_tab_action);
+
+        // Move the current tab one place left or right
+        for (name, step) in [("move-tab-left", -1), ("move-tab-right", 1)] {
+            let move_action = SimpleAction::new(name, None);
+            let notebook_clone = notebook.clone();
+            let tabs_clone = tabs.clone();
+            move_action.connect_activate(move |_, _| {
+                let Some(current) = notebook_clone.current_page() else {
+                    return;
+                };
+                let target = current as i32 + step;
+                if target < 0 || target >= notebook_clone.n_pages() as i32 {
+                    return;
+                }
+                if let Some(page) = notebook_clone.nth_page(Some(current)) {
+                    notebook_clone.reorder_child(&page, Some(target as u32));
+                    Self::sync_tab_order(&notebook_clone, &tabs_clone);
+                }
+            });
+            window.add_action(&move_action);
+        }
+
+        // Font zoom of the focused terminal
+        for (name, factor) in [("zoom-in", Some(ZOOM_STEP)), ("zoom-out", Some(1.0 / ZOOM_STEP)), ("zoom-reset", None)] {
+            let zoom_action = SimpleAction::new(name, None);
+            let tabs_clone = tabs.clone();
+            let notebook_clone = notebook.clone();
+            zoom_action.connect_activate(move |_, _| {
+                if let Some(tab) = Self::current_tab(&notebook_clone, &tabs_clone) {
+                    let terminal = &tab.borrow().active_pane().terminal;
+                    match factor {
+                        Some(factor) => terminal.zoom(factor),
+                        None => terminal.reset_zoom(),
+                    }
+                }
+            });
+            window.add_action(&zoom_action);
+        }
+
+        // Bytes typed into the focused terminal by a keybinding
+        let send_bytes_action = SimpleAction::new("send-bytes", Some(glib::VariantTy::BYTE_STRING));
+        let tabs_clone = tabs.clone();
+        let notebook_clone = notebook.clone();
+        send_bytes_action.connect_activate(move |_, param| {
+            if let (Some(bytes), Some(tab)) = (
+                param.and_then(|p| p.get::<Vec<u8>>()),
+                Self::current_tab(&notebook_clone, &tabs_clone),
+            ) {
+                tab.borrow().active_pane().terminal.send_bytes(&bytes);
+            }
+        });
+        window.add_action(&send_bytes_action);
+    }
+
+    /// Import a color scheme file, add it to the Themes submenu of every
+    /// window and apply it.
+    fn import_theme(
+        path: &std::path::Path,
+        banner: &Banner,
+        windows: &Rc<RefCell<Vec<Rc<RustyTermWindow>>>>,
+        set_theme_action: &SimpleAction,
+    ) {
+        let mut theme = match import_file(path) {
+            Ok(theme) => theme,
+            Err(e) => {
+                banner.show_error(&format!("Failed to import {}: {}", path.display(), e));
+                return;
+            }
+        };
+
+        let saved = match save_theme(&mut theme) {
+            Ok(saved) => saved,
+            Err(e) => {
+                banner.show_error(&format!("Failed to save theme \"{}\": {}", theme.name, e));
+                return;
+            }
+        };
+        // Saving may rename the theme, so look it up before reloading
+        let is_new = get_theme_by_name(&theme.name).is_none();
+        load_themes();
+        banner.show_info(&format!("Imported theme \"{}\" to {}", theme.name, saved.display()));
+
+        if is_new {
+            let item = MenuItem::new(Some(&theme.name), Some(&format!("win.set-theme::{}", theme.name)));
+            for window in windows.borrow().iter() {
+                window.theme_menu.append_item(&item);
+            }
+        }
+        set_theme_action.activate(Some(&theme.name.to_variant()));
+    }
+
+    /// Write a theme to `path`, choosing the format from the file name.
+    fn export_theme(path: &std::path::Path, theme: &Theme, banner: &Banner) {
+        let Some(format) = ExportFormat::from_path(path) else {
+            banner.show_error(&format!(
+                "Cannot export to {}: use a .toml (Alacritty), .conf (kitty), .json (Windows Terminal), \
+                 .sh (shell script) or Xresources file name",
+                path.display()
+            ));
+            return;
+        };
+
+        let result = export_theme(theme, format)
+            .map_err(|e| e.to_string())
+            .and_then(|content| std::fs::write(path, content).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            banner.show_error(&format!("Failed to export theme to {}: {}", path.display(), e));
+        }
     }
 
     fn setup_new_tab_button(&self, button: &Button) {
@@ -180,31 +809,132 @@ impl RustyTermWindow {
         let window = self.window.clone();
 
         button.connect_clicked(move |_| {
-            Self::create_new_tab(&notebook, &config, &tabs, &window);
+            Self::create_new_tab(&notebook, &config, &tabs, &window, &TabOptions::default());
         });
     }
 
     fn setup_notebook_signals(&self) {
         // Handle page switch for focus
-        self.notebook.connect_switch_page(move |nb, _, page_num| {
-            if let Some(page) = nb.nth_page(Some(page_num)) {
-                page.grab_focus();
+        let tabs = self.tabs.clone();
+        let recent_tabs = self.recent_tabs.clone();
+        self.notebook.connect_switch_page(move |_, page, _| {
+            let tab = tabs.borrow().iter()
+                .find(|t| t.borrow().container.upcast_ref::<gtk4::Widget>() == page)
+                .cloned();
+            match tab {
+                Some(tab) => {
+                    // Forget closed tabs and move this one to the front
+                    let id = tab.borrow().id;
+                    let mut recent = recent_tabs.borrow_mut();
+                    recent.retain(|recent_id| {
+                        *recent_id != id && tabs.borrow().iter().any(|t| t.borrow().id == *recent_id)
+                    });
+                    recent.insert(0, id);
+                    drop(recent);
+
+                    let pane = tab.borrow().active_pane();
+                    pane.terminal.widget().grab_focus();
+                }
+                None => {
+                    page.grab_focus();
+                }
             }
         });
+
+        // Tabs dragged to another place keep their index in `tabs` in step
+        let tabs = self.tabs.clone();
+        self.notebook.connect_page_reordered(move |notebook, _, _| {
+            Self::sync_tab_order(notebook, &tabs);
+        });
+    }
+
+    /// Apply a new configuration to the window's terminals and menus.
+    /// Terminals whose profile sets its own theme keep it.
+    pub fn apply_config(&self, config: &Config) {
+        for tab in self.tabs.borrow().iter() {
+            for pane in tab.borrow().panes() {
+                pane.terminal.apply_config(config);
+            }
+        }
+        Self::fill_profile_menu(&self.profile_menu, config);
+    }
+
+    /// Whether the config of this window defines the profile `name`.
+    pub fn has_profile(&self, name: &str) -> bool {
+        self.config.borrow().has_profile(name)
+    }
+
+    pub fn add_tab(&self, options: &TabOptions) -> Rc<RefCell<Tab>> {
+        Self::create_new_tab(&self.notebook, &self.config, &self.tabs, &self.window, options)
+    }
+
+    /// The window's tabs, in the order they were opened.
+    pub fn tabs(&self) -> Vec<Rc<RefCell<Tab>>> {
+        self.tabs.borrow().clone()
+    }
+
+    /// The tab currently shown.
+    pub fn current(&self) -> Option<Rc<RefCell<Tab>>> {
+        Self::current_tab(&self.notebook, &self.tabs)
     }
 
-    fn add_tab(&self) {
-        Self::create_new_tab(&self.notebook, &self.config, &self.tabs, &self.window);
+    /// Switch to the tab at `index`, returning false if there is none.
+    pub fn focus_tab(&self, index: usize) -> bool {
+        if index >= self.tabs.borrow().len() {
+            return false;
+        }
+        self.notebook.set_current_page(Some(index as u32));
+        true
     }
 
+    /// Open a tab as described by `options`, using the default profile
+    /// unless they name another.
     fn create_new_tab(
         notebook: &Notebook,
         config: &Rc<RefCell<Config>>,
         tabs: &Rc<RefCell<Vec<Rc<RefCell<Tab>>>>>,
         window: &ApplicationWindow,
-    ) {
-        let tab = Tab::new(&config.borrow());
+        options: &TabOptions,
+    ) -> Rc<RefCell<Tab>> {
+        let focused = Self::current_tab(notebook, tabs).map(|tab| tab.borrow().active_pane());
+        let (profile, spawn) = {
+            let config = config.borrow();
+            let mut profile = config.profile(options.profile.as_deref().unwrap_or(&config.default_profile));
+            if options.hold {
+                profile.on_exit = ExitAction::Hold;
+            }
+
+            let mut spawn = SpawnOptions::from_profile(&profile);
+            if let Some(command) = &options.command {
+                spawn.command = command.clone();
+                spawn.login_shell = false;
+            }
+            if let Some(dir) = &options.working_directory {
+                spawn.working_directory = Some(dir.clone());
+            }
+            let inherited = options.parent_directory.clone()
+                .or_else(|| focused.and_then(|pane| pane.terminal.current_directory()));
+            spawn.inherit_directory(inherited, &config);
+            (profile, spawn)
+        };
+        let tab = Tab::new(&profile, &spawn, &config.borrow());
+        if options.title.is_some() {
+            tab.borrow().set_custom_title(options.title.clone());
+        }
+        Self::insert_tab(notebook, config, tabs, window, &tab);
+        tab
+    }
 
+    /// Append a tab to the notebook and switch to it.
+    fn insert_tab(
+        notebook: &Notebook,
+        config: &Rc<RefCell<Config>>,
+        tabs: &Rc<RefCell<Vec<Rc<RefCell<Tab>>>>>,
+        window: &ApplicationWindow,
+        tab: &Rc<RefCell<Tab>>,
+    ) {
+        // Known before the page switches to it, so it counts as used
+        tabs.borrow_mut().push(tab.clone());
         let page_num = notebook.append_page(
             &tab.borrow().container,
             Some(&tab.borrow().label_box),
@@ -212,27 +942,70 @@ impl RustyTermWindow {
 
         notebook.set_tab_reorderable(&tab.borrow().container, true);
         notebook.set_current_page(Some(page_num));
-        tab.borrow().terminal.widget().grab_focus();
+        tab.borrow().active_pane().terminal.widget().grab_focus();
 
         // Setup close callback
+        let config_clone = config.clone();
         let notebook_clone = notebook.clone();
         let tabs_clone = tabs.clone();
         let window_clone = window.clone();
-        let tab_weak = Rc::downgrade(&tab);
+        let tab_weak = Rc::downgrade(tab);
         tab.borrow().set_close_callback(move || {
             if let Some(tab) = tab_weak.upgrade() {
                 let idx = tabs_clone.borrow().iter()
                     .position(|t| Rc::ptr_eq(t, &tab));
                 if let Some(idx) = idx {
-                    Self::close_tab_at(&notebook_clone, &tabs_clone, &window_clone, idx);
+                    Self::close_tab_at(&notebook_clone, &tabs_clone, &window_clone, &config_clone, idx);
                 }
             }
         });
+    }
 
-        tabs.borrow_mut().push(tab);
+    /// Sort `tabs` into the order of the notebook pages, which the
+    /// index-based lookups rely on.
+    fn sync_tab_order(notebook: &Notebook, tabs: &Rc<RefCell<Vec<Rc<RefCell<Tab>>>>>) {
+        tabs.borrow_mut().sort_by_key(|tab| notebook.page_num(&tab.borrow().container));
     }
 
+    fn current_tab(
+        notebook: &Notebook,
+        tabs: &Rc<RefCell<Vec<Rc<RefCell<Tab>>>>>,
+    ) -> Option<Rc<RefCell<Tab>>> {
+        let idx = notebook.current_page()?;
+        tabs.borrow().get(idx as usize).cloned()
+    }
+
+    /// Close the tab at `idx`, asking first if a command is running in it.
     fn close_tab_at(
+        notebook: &Notebook,
+        tabs: &Rc<RefCell<Vec<Rc<RefCell<Tab>>>>>,
+        window: &ApplicationWindow,
+        config: &Rc<RefCell<Config>>,
+        idx: usize,
+    ) {
+        if tabs.borrow().len() <= 1 {
+            // The window asks for confirmation itself
+            window.close();
+            return;
+        }
+        let Some(tab) = tabs.borrow().get(idx).cloned() else {
+            return;
+        };
+
+        let processes = Self::processes_in(tab.borrow().panes(), &config.borrow());
+        let notebook = notebook.clone();
+        let tabs = tabs.clone();
+        let window_clone = window.clone();
+        Self::confirm_close(window, "Close this tab?", &processes, move || {
+            // Other tabs may have closed while the dialog was open
+            let idx = tabs.borrow().iter().position(|t| Rc::ptr_eq(t, &tab));
+            if let Some(idx) = idx {
+                Self::remove_tab_at(&notebook, &tabs, &window_clone, idx);
+            }
+        });
+    }
+
+    fn remove_tab_at(
         notebook: &Notebook,
         tabs: &Rc<RefCell<Vec<Rc<RefCell<Tab>>>>>,
         window: &ApplicationWindow,
@@ -253,6 +1026,92 @@ impl RustyTermWindow {
         notebook.set_current_page(Some(new_idx as u32));
     }
 
+    /// Ask before closing the window while commands are running in it.
+    fn setup_close_confirmation(&self) {
+        let config = self.config.clone();
+        let tabs = self.tabs.clone();
+        let confirmed = Rc::new(Cell::new(false));
+        self.window.connect_close_request(move |window| {
+            if confirmed.get() {
+                return glib::Propagation::Proceed;
+            }
+            let processes = Self::processes_in(&Self::all_panes(&tabs), &config.borrow());
+            if processes.is_empty() {
+                return glib::Propagation::Proceed;
+            }
+
+            let window_clone = window.clone();
+            let confirmed = confirmed.clone();
+            Self::confirm_close(window, "Close this window?", &processes, move || {
+                confirmed.set(true);
+                window_clone.close();
+            });
+            glib::Propagation::Stop
+        });
+    }
+
+    /// Foreground commands that closing the window would terminate.
+    pub fn running_processes(&self) -> Vec<String> {
+        Self::processes_in(&Self::all_panes(&self.tabs), &self.config.borrow())
+    }
+
+    fn all_panes(tabs: &Rc<RefCell<Vec<Rc<RefCell<Tab>>>>>) -> Vec<Rc<Pane>> {
+        tabs.borrow().iter()
+            .flat_map(|tab| tab.borrow().panes().to_vec())
+            .collect()
+    }
+
+    /// Foreground commands in `panes` that closing them would terminate.
+    fn processes_in(panes: &[Rc<Pane>], config: &Config) -> Vec<String> {
+        panes.iter()
+            .filter_map(|pane| pane.terminal.foreground_process())
+            .filter(|name| !config.confirm_close_ignore.contains(name))
+            .collect()
+    }
+
+    /// Run `on_confirm` right away if no command is running, or once the
+    /// user agrees to terminate them.
+    pub fn confirm_close<F>(window: &ApplicationWindow, question: &str, processes: &[String], on_confirm: F)
+    where
+        F: FnOnce() + 'static,
+    {
+        if processes.is_empty() {
+            on_confirm();
+            return;
+        }
+
+        let names: Vec<String> = processes.iter().map(|name| format!("“{}”", name)).collect();
+        let detail = if names.len() == 1 {
+            format!("{} is still running and will be terminated.", names[0])
+        } else {
+            format!("{} are still running and will be terminated.", names.join(", "))
+        };
+
+        let dialog = MessageDialog::new(
+            Some(window),
+            DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+            MessageType::Question,
+            ButtonsType::None,
+            question,
+        );
+        dialog.set_secondary_text(Some(&detail));
+        dialog.add_button("_Cancel", ResponseType::Cancel);
+        let close_button = dialog.add_button("C_lose", ResponseType::Accept);
+        close_button.add_css_class("destructive-action");
+        dialog.set_default_response(ResponseType::Cancel);
+
+        let on_confirm = Cell::new(Some(on_confirm));
+        dialog.connect_response(move |dialog, response| {
+            dialog.close();
+            if response == ResponseType::Accept
+                && let Some(on_confirm) = on_confirm.take()
+            {
+                on_confirm();
+            }
+        });
+        dialog.present();
+    }
+
     pub fn present(&self) {
         self.window.present();
     }
@@ -0,0 +1,58 @@
+#!/bin/sh
+# Check that later launches forward their command line to the running
+# instance. Run it on a private session bus so it never reaches a rustyterm
+# already open on the desktop:
+#
+#   cargo build
+#   dbus-run-session -- tests/remote-control.sh [path/to/rustyterm]
+#
+# A display is needed; on a headless machine wrap the whole command in
+# `xvfb-run -a`.
+set -eu
+
+RUSTYTERM=${1:-target/debug/rustyterm}
+APP_ID=com.github.rustyterm
+
+if [ -z "${DBUS_SESSION_BUS_ADDRESS:-}" ]; then
+    echo "Run this script through dbus-run-session" >&2
+    exit 2
+fi
+
+# Keep the config and session files of the developer out of the test
+XDG_CONFIG_HOME=$(mktemp -d)
+export XDG_CONFIG_HOME
+
+"$RUSTYTERM" -e sleep 300 &
+PRIMARY=$!
+trap 'kill "$PRIMARY" 2>/dev/null || true; rm -rf "$XDG_CONFIG_HOME"' EXIT
+
+fail() {
+    echo "FAIL: $*" >&2
+    exit 1
+}
+
+# Wait for the first instance to own the application name
+for _ in $(seq 50); do
+    if gdbus call --session --dest org.freedesktop.DBus \
+        --object-path /org/freedesktop/DBus \
+        --method org.freedesktop.DBus.NameHasOwner "$APP_ID" | grep -q true; then
+        break
+    fi
+    sleep 0.1
+done
+
+"$RUSTYTERM" --new-tab -e sleep 300 || fail "--new-tab"
+# Only the first instance knows about the new tab, so this also proves
+# the request was forwarded
+"$RUSTYTERM" --focus-tab 2 || fail "--focus-tab 2"
+if "$RUSTYTERM" --focus-tab 3; then
+    fail "--focus-tab 3 succeeded with only two tabs"
+fi
+"$RUSTYTERM" --new-window -e sleep 300 || fail "--new-window"
+"$RUSTYTERM" --focus-tab 1 || fail "--focus-tab 1 in the new window"
+if "$RUSTYTERM" --focus-tab 2; then
+    fail "--focus-tab 2 succeeded in a window with one tab"
+fi
+
+kill -0 "$PRIMARY" || fail "first instance exited"
+echo "OK"